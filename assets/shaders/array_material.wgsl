@@ -0,0 +1,42 @@
+// Single material for every mesh instance, the base color texture is picked from a texture array
+// by hashing the instance's translation. Lit like simple_material.wgsl for comparison.
+
+#import bevy_pbr::{
+    forward_io::VertexOutput,
+    mesh_functions::get_world_from_local,
+}
+
+struct ArrayMaterial {
+    layer_count: u32,
+}
+
+@group(2) @binding(0) var<uniform> material: ArrayMaterial;
+@group(2) @binding(1) var base_color_texture: texture_2d_array<f32>;
+@group(2) @binding(2) var base_color_sampler: sampler;
+
+fn hash(v: vec3<u32>) -> u32 {
+    var h = (v.x * 0x9e3779b1u) ^ (v.y * 0x85ebca77u) ^ (v.z * 0xc2b2ae3du);
+    h ^= h >> 15u;
+    h *= 0x2c1b3c6du;
+    h ^= h >> 12u;
+    return h;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let world_from_local = get_world_from_local(in.instance_index);
+    let instance_hash = hash(bitcast<vec3<u32>>(world_from_local[3].xyz));
+    let layer = instance_hash % material.layer_count;
+
+    var color = vec4(
+        f32(instance_hash & 0xffu),
+        f32((instance_hash >> 8u) & 0xffu),
+        f32((instance_hash >> 16u) & 0xffu),
+        255.0,
+    ) / 255.0;
+#ifdef VERTEX_UVS_A
+    color *= textureSample(base_color_texture, base_color_sampler, in.uv, layer);
+#endif
+    let light = max(dot(normalize(in.world_normal), normalize(vec3(0.3, 1.0, 0.2))), 0.0);
+    return vec4(color.rgb * (light * 0.8 + 0.2), color.a);
+}
@@ -0,0 +1,25 @@
+// Unlit debug views, see src/debug_views.rs.
+
+#import bevy_pbr::forward_io::VertexOutput
+
+struct DebugMaterial {
+    color: vec4<f32>,
+    // 0: color (and texture), 1: world normals
+    mode: u32,
+}
+
+@group(2) @binding(0) var<uniform> material: DebugMaterial;
+@group(2) @binding(1) var base_color_texture: texture_2d<f32>;
+@group(2) @binding(2) var base_color_sampler: sampler;
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    if material.mode == 1u {
+        return vec4(normalize(in.world_normal) * 0.5 + 0.5, 1.0);
+    }
+    var color = material.color;
+#ifdef VERTEX_UVS_A
+    color *= textureSample(base_color_texture, base_color_sampler, in.uv);
+#endif
+    return color;
+}
@@ -0,0 +1,26 @@
+// Minimal material: base color texture with a fixed directional light, no PBR.
+
+#import bevy_pbr::forward_io::VertexOutput
+
+struct SimpleMaterial {
+    color: vec4<f32>,
+    // 0.0 unless alpha masked.
+    alpha_cutoff: f32,
+}
+
+@group(2) @binding(0) var<uniform> material: SimpleMaterial;
+@group(2) @binding(1) var base_color_texture: texture_2d<f32>;
+@group(2) @binding(2) var base_color_sampler: sampler;
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = material.color;
+#ifdef VERTEX_UVS_A
+    color *= textureSample(base_color_texture, base_color_sampler, in.uv);
+#endif
+    if color.a < material.alpha_cutoff {
+        discard;
+    }
+    let light = max(dot(normalize(in.world_normal), normalize(vec3(0.3, 1.0, 0.2))), 0.0);
+    return vec4(color.rgb * (light * 0.8 + 0.2), color.a);
+}
@@ -0,0 +1,51 @@
+// Prepass for the simple material: cuts out alpha masked texels so depth, normals and shadows match
+// the main pass.
+
+#import bevy_pbr::{
+    prepass_io::{VertexOutput, FragmentOutput},
+    pbr_prepass_functions::calculate_motion_vector,
+}
+
+struct SimpleMaterial {
+    color: vec4<f32>,
+    // 0.0 unless alpha masked.
+    alpha_cutoff: f32,
+}
+
+@group(2) @binding(0) var<uniform> material: SimpleMaterial;
+@group(2) @binding(1) var base_color_texture: texture_2d<f32>;
+@group(2) @binding(2) var base_color_sampler: sampler;
+
+fn discard_masked(in: VertexOutput) {
+    var alpha = material.color.a;
+#ifdef VERTEX_UVS_A
+    alpha *= textureSample(base_color_texture, base_color_sampler, in.uv).a;
+#endif
+    if alpha < material.alpha_cutoff {
+        discard;
+    }
+}
+
+#ifdef PREPASS_FRAGMENT
+@fragment
+fn fragment(in: VertexOutput) -> FragmentOutput {
+    discard_masked(in);
+
+    var out: FragmentOutput;
+#ifdef NORMAL_PREPASS
+    out.normal = vec4(in.world_normal * 0.5 + vec3(0.5), 1.0);
+#endif
+#ifdef DEPTH_CLAMP_ORTHO
+    out.frag_depth = in.clip_position_unclamped.z;
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    out.motion_vector = calculate_motion_vector(in.world_position, in.previous_world_position);
+#endif
+    return out;
+}
+#else
+@fragment
+fn fragment(in: VertexOutput) {
+    discard_masked(in);
+}
+#endif
@@ -0,0 +1,231 @@
+// Bounds of mesh instances, the total scene bound and a BVH over the instances, computed once
+// since the scene is static.
+// Bevy doesn't calculate an `Aabb` for entities with `NoFrustumCulling`, so the mesh is used when
+// it's missing.
+
+use std::time::Instant;
+
+use bevy::{
+    math::{
+        bounding::{Aabb3d, BoundingVolume, RayCast3d},
+        Vec3A,
+    },
+    prelude::*,
+    render::primitives::Aabb,
+};
+
+use crate::SceneLoaded;
+
+/// World space bounding sphere of a mesh instance.
+#[derive(Component, Clone, Copy)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Encloses the local space `aabb` transformed to world space, without decomposing the
+    /// transform: the radius is scaled by the longest axis of the transform's matrix.
+    pub fn new(aabb: &Aabb, transform: &GlobalTransform) -> Self {
+        let matrix = transform.affine().matrix3;
+        let scale = matrix
+            .x_axis
+            .length()
+            .max(matrix.y_axis.length())
+            .max(matrix.z_axis.length());
+        Self {
+            center: transform.transform_point(aabb.center.into()),
+            radius: aabb.half_extents.length() * scale,
+        }
+    }
+}
+
+/// Local space bounds of a mesh instance: its `Aabb` when bevy calculated one, otherwise computed
+/// from the mesh.
+pub fn mesh_aabb(aabb: Option<&Aabb>, mesh: &Handle<Mesh>, meshes: &Assets<Mesh>) -> Option<Aabb> {
+    aabb.copied().or_else(|| meshes.get(mesh)?.compute_aabb())
+}
+
+// Max mesh instances per BVH leaf.
+const BVH_LEAF_SIZE: usize = 4;
+
+#[derive(Resource)]
+pub struct SceneBounds {
+    /// World space Aabb of each mesh instance.
+    pub instances: Vec<(Entity, Aabb3d)>,
+    /// Union of all instance bounds.
+    pub total: Aabb3d,
+    pub bvh: Bvh,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum BvhNodeKind {
+    /// Indices of the child nodes.
+    Branch(usize, usize),
+    /// Range of `Bvh::entities`.
+    Leaf(usize, usize),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BvhNode {
+    pub bounds: Aabb3d,
+    pub kind: BvhNodeKind,
+}
+
+/// Bounding volume hierarchy over mesh instance bounds, split at the median of the longest axis.
+/// The root is the first node.
+#[derive(Default)]
+pub struct Bvh {
+    pub nodes: Vec<BvhNode>,
+    /// Mesh instances and their world space bounds, in leaf order.
+    pub entities: Vec<(Entity, Aabb3d)>,
+}
+
+impl Bvh {
+    pub fn new(instances: &[(Entity, Aabb3d)]) -> Self {
+        let mut bvh = Bvh::default();
+        if instances.is_empty() {
+            return bvh;
+        }
+        let mut items = instances.to_vec();
+        bvh.build(&mut items, 0);
+        bvh
+    }
+
+    // Returns the index of the node covering `items`, `first` is the index of items[0] in `entities`.
+    fn build(&mut self, items: &mut [(Entity, Aabb3d)], first: usize) -> usize {
+        let bounds = merge_all(items.iter().map(|(_, aabb)| *aabb));
+        let index = self.nodes.len();
+        self.nodes.push(BvhNode {
+            bounds,
+            kind: BvhNodeKind::Leaf(first, first + items.len()),
+        });
+        if items.len() <= BVH_LEAF_SIZE {
+            self.entities.extend_from_slice(items);
+            return index;
+        }
+
+        let size = bounds.max - bounds.min;
+        let axis = if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        };
+        let mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |a, b| {
+            a.1.center()[axis].total_cmp(&b.1.center()[axis])
+        });
+        let (left, right) = items.split_at_mut(mid);
+        let left = self.build(left, first);
+        let right = self.build(right, first + mid);
+        self.nodes[index].kind = BvhNodeKind::Branch(left, right);
+        index
+    }
+
+    /// Entities whose bounds are hit by the ray, and the distance to each (0.0 when the ray starts
+    /// inside).
+    pub fn ray_candidates(&self, ray: Ray3d) -> Vec<(Entity, f32)> {
+        let ray_cast = RayCast3d::from_ray(ray, f32::MAX);
+        let mut hits = Vec::new();
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let Some(node) = self.nodes.get(index) else {
+                continue;
+            };
+            if ray_cast.aabb_intersection_at(&node.bounds).is_none() {
+                continue;
+            }
+            match node.kind {
+                BvhNodeKind::Branch(left, right) => stack.extend([left, right]),
+                BvhNodeKind::Leaf(start, end) => hits.extend(
+                    self.entities[start..end]
+                        .iter()
+                        .filter_map(|(entity, aabb)| {
+                            Some((*entity, ray_cast.aabb_intersection_at(aabb)?))
+                        }),
+                ),
+            }
+        }
+        hits
+    }
+
+    pub fn depth(&self) -> usize {
+        fn depth(nodes: &[BvhNode], index: usize) -> usize {
+            match nodes[index].kind {
+                BvhNodeKind::Branch(left, right) => 1 + depth(nodes, left).max(depth(nodes, right)),
+                BvhNodeKind::Leaf(..) => 1,
+            }
+        }
+        if self.nodes.is_empty() {
+            0
+        } else {
+            depth(&self.nodes, 0)
+        }
+    }
+}
+
+fn merge_all(aabbs: impl Iterator<Item = Aabb3d>) -> Aabb3d {
+    aabbs
+        .reduce(|a, b| a.merge(&b))
+        .unwrap_or(Aabb3d::new(Vec3A::ZERO, Vec3A::ZERO))
+}
+
+/// World space bounds of a local space `Aabb`.
+pub fn world_aabb(transform: &GlobalTransform, aabb: &Aabb) -> Aabb3d {
+    let affine = transform.affine();
+    let center = affine.transform_point3a(aabb.center);
+    let abs = affine.matrix3;
+    let half_extents = abs.x_axis.abs() * aabb.half_extents.x
+        + abs.y_axis.abs() * aabb.half_extents.y
+        + abs.z_axis.abs() * aabb.half_extents.z;
+    Aabb3d::new(center, half_extents)
+}
+
+pub struct SceneBoundsPlugin;
+
+impl Plugin for SceneBoundsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            compute_scene_bounds.run_if(
+                resource_exists::<SceneLoaded>.and_then(not(resource_exists::<SceneBounds>)),
+            ),
+        );
+    }
+}
+
+pub fn compute_scene_bounds(
+    mut commands: Commands,
+    instances: Query<(Entity, &GlobalTransform, Option<&Aabb>, &Handle<Mesh>)>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    let start = Instant::now();
+    let instances = instances
+        .iter()
+        .filter_map(|(entity, transform, aabb, mesh)| {
+            let aabb = mesh_aabb(aabb, mesh, &meshes)?;
+            Some((entity, world_aabb(transform, &aabb)))
+        })
+        .collect::<Vec<_>>();
+    let total = merge_all(instances.iter().map(|(_, aabb)| *aabb));
+    let bvh = Bvh::new(&instances);
+
+    let size = total.max - total.min;
+    println!(
+        "Scene bounds: min {}, max {}, size {} ({} mesh instances, BVH with {} nodes and depth {}, {:.2}ms)",
+        total.min,
+        total.max,
+        size,
+        instances.len(),
+        bvh.nodes.len(),
+        bvh.depth(),
+        start.elapsed().as_secs_f32() * 1000.0
+    );
+    commands.insert_resource(SceneBounds {
+        instances,
+        total,
+        bvh,
+    });
+}
@@ -60,6 +60,20 @@ impl CameraController {
         );
         self
     }
+
+    /// Moves the camera to `new_transform` and resynchronizes the controller state with it, so the
+    /// next mouse move continues from the new rotation instead of snapping back.
+    /// The orbit focus stays at the same distance in front of the camera.
+    pub fn set_transform(&mut self, transform: &mut Transform, new_transform: Transform) {
+        let orbit_distance = transform.translation.distance(self.orbit_focus);
+        *transform = new_transform;
+        let (_roll, yaw, pitch) = transform.rotation.to_euler(EulerRot::ZYX);
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self.orbit_focus = transform.translation + *transform.forward() * orbit_distance;
+        self.velocity = Vec3::ZERO;
+        self.initialized = true;
+    }
 }
 
 impl Default for CameraController {
@@ -0,0 +1,94 @@
+// Distance culling using bevy's `VisibilityRange`.
+// Note: bevy measures the distance from the camera to the entity's translation, not its bounds.
+
+use bevy::{
+    prelude::*,
+    render::{
+        primitives::Aabb,
+        view::{VisibilityRange, VisibilitySystems},
+    },
+};
+
+use crate::bounds::{mesh_aabb, BoundingSphere};
+
+#[derive(Resource, Clone)]
+pub struct DistanceCullingSettings {
+    /// Absolute max draw distance for every mesh instance. 0.0 to disable.
+    pub max_distance: f32,
+    /// Max draw distance as a multiple of each instance's bounding sphere radius. 0.0 to disable.
+    /// If both are set the smaller distance is used.
+    pub radius_scale: f32,
+}
+
+impl DistanceCullingSettings {
+    pub fn draw_distance(&self, radius: f32) -> f32 {
+        let scaled = radius * self.radius_scale;
+        match (self.max_distance > 0.0, self.radius_scale > 0.0) {
+            (true, true) => self.max_distance.min(scaled),
+            (true, false) => self.max_distance,
+            _ => scaled,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct DistanceCullingStats {
+    /// Mesh instances beyond their max draw distance from the camera this frame.
+    pub culled: usize,
+    /// Mesh instances with a max draw distance.
+    pub total: usize,
+}
+
+pub struct DistanceCullingPlugin {
+    pub settings: DistanceCullingSettings,
+}
+
+impl Plugin for DistanceCullingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone())
+            .init_resource::<DistanceCullingStats>()
+            .add_systems(Update, add_visibility_ranges)
+            .add_systems(
+                PostUpdate,
+                count_distance_culled.after(VisibilitySystems::CheckVisibility),
+            );
+    }
+}
+
+// Scenes spawn after Update, so ranges are added the frame after a mesh instance spawns, once its
+// `GlobalTransform` has been propagated.
+#[allow(clippy::type_complexity)]
+pub fn add_visibility_ranges(
+    mut commands: Commands,
+    meshes: Res<Assets<Mesh>>,
+    instances: Query<(Entity, &GlobalTransform, &Handle<Mesh>, Option<&Aabb>), Added<Handle<Mesh>>>,
+    settings: Res<DistanceCullingSettings>,
+) {
+    for (entity, transform, mesh_h, aabb) in &instances {
+        let Some(aabb) = mesh_aabb(aabb, mesh_h, &meshes) else {
+            continue;
+        };
+        let radius = BoundingSphere::new(&aabb, transform).radius;
+        commands
+            .entity(entity)
+            .insert(VisibilityRange::abrupt(0.0, settings.draw_distance(radius)));
+    }
+}
+
+pub fn count_distance_culled(
+    camera: Query<&GlobalTransform, With<Camera3d>>,
+    instances: Query<(&GlobalTransform, &VisibilityRange)>,
+    mut stats: ResMut<DistanceCullingStats>,
+) {
+    let Ok(camera_transform) = camera.get_single() else {
+        return;
+    };
+    let camera_position = camera_transform.translation();
+    stats.total = instances.iter().len();
+    stats.culled = instances
+        .iter()
+        .filter(|(transform, range)| {
+            range.is_culled(transform.translation().distance(camera_position))
+        })
+        .count();
+}
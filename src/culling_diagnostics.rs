@@ -0,0 +1,175 @@
+// Per frame culling counts exposed as `Diagnostic`s, so they show up in `LogDiagnosticsPlugin`
+// output and the benchmark report.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use bevy::{
+    core_pipeline::{
+        core_3d::{AlphaMask3d, Opaque3d, Transmissive3d, Transparent3d},
+        deferred::{AlphaMask3dDeferred, Opaque3dDeferred},
+        prepass::{AlphaMask3dPrepass, Opaque3dPrepass},
+    },
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    pbr::{CascadesVisibleEntities, Shadow, SimulationLightSystems},
+    prelude::*,
+    render::{
+        render_phase::{
+            BinnedPhaseItem, SortedPhaseItem, ViewBinnedRenderPhases, ViewSortedRenderPhases,
+        },
+        view::{VisibilitySystems, VisibleEntities, WithMesh},
+        Render, RenderApp, RenderSet,
+    },
+};
+
+pub const MESH_ENTITIES: DiagnosticPath = DiagnosticPath::const_new("culling/mesh_entities");
+// Meshes in the cameras' `VisibleEntities`, after frustum and distance culling. Instances hidden
+// later by occlusion culling are still counted, see `OcclusionCullingStats` for those.
+pub const VISIBLE_MESHES: DiagnosticPath = DiagnosticPath::const_new("culling/visible_meshes");
+pub const DRAW_CALLS: DiagnosticPath = DiagnosticPath::const_new("culling/draw_calls");
+pub const SHADOW_DRAW_CALLS: DiagnosticPath =
+    DiagnosticPath::const_new("culling/shadow_draw_calls");
+// Bevy supports at most 4 cascades, only the ones that exist are measured.
+pub const SHADOW_CASTERS: [DiagnosticPath; 4] = [
+    DiagnosticPath::const_new("culling/shadow_casters_cascade_0"),
+    DiagnosticPath::const_new("culling/shadow_casters_cascade_1"),
+    DiagnosticPath::const_new("culling/shadow_casters_cascade_2"),
+    DiagnosticPath::const_new("culling/shadow_casters_cascade_3"),
+];
+
+// Draw call counts written by the render world and read back in the main world the next frame.
+#[derive(Resource, Clone, Default)]
+struct DrawCallCounts {
+    main: Arc<AtomicUsize>,
+    shadow: Arc<AtomicUsize>,
+}
+
+pub struct CullingDiagnosticsPlugin;
+
+impl Plugin for CullingDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        let draw_call_counts = DrawCallCounts::default();
+        app.register_diagnostic(Diagnostic::new(MESH_ENTITIES))
+            .register_diagnostic(Diagnostic::new(VISIBLE_MESHES))
+            .register_diagnostic(Diagnostic::new(DRAW_CALLS))
+            .register_diagnostic(Diagnostic::new(SHADOW_DRAW_CALLS))
+            .insert_resource(draw_call_counts.clone())
+            .add_systems(
+                PostUpdate,
+                measure_culling
+                    .after(VisibilitySystems::CheckVisibility)
+                    // Cascade visible entities are written by light visibility.
+                    .after(SimulationLightSystems::CheckLightVisibility),
+            );
+        for path in SHADOW_CASTERS {
+            app.register_diagnostic(Diagnostic::new(path));
+        }
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .insert_resource(draw_call_counts)
+            .add_systems(Render, count_draw_calls.in_set(RenderSet::Render));
+    }
+}
+
+fn measure_culling(
+    mut diagnostics: Diagnostics,
+    mesh_entities: Query<(), With<Handle<Mesh>>>,
+    cameras: Query<&VisibleEntities, With<Camera3d>>,
+    cascades: Query<&CascadesVisibleEntities>,
+    draw_call_counts: Res<DrawCallCounts>,
+) {
+    diagnostics.add_measurement(&MESH_ENTITIES, || mesh_entities.iter().len() as f64);
+    diagnostics.add_measurement(&VISIBLE_MESHES, || {
+        cameras
+            .iter()
+            .map(|visible| visible.len::<WithMesh>())
+            .sum::<usize>() as f64
+    });
+    diagnostics.add_measurement(&DRAW_CALLS, || {
+        draw_call_counts.main.load(Ordering::Relaxed) as f64
+    });
+    diagnostics.add_measurement(&SHADOW_DRAW_CALLS, || {
+        draw_call_counts.shadow.load(Ordering::Relaxed) as f64
+    });
+
+    // Summed over all views, each view has its own set of cascades.
+    let mut casters = [None; 4];
+    for cascades in &cascades {
+        for view_cascades in cascades.entities.values() {
+            for (count, cascade) in casters.iter_mut().zip(view_cascades) {
+                *count = Some(count.unwrap_or(0) + cascade.len::<WithMesh>());
+            }
+        }
+    }
+    for (path, count) in SHADOW_CASTERS.iter().zip(casters) {
+        if let Some(count) = count {
+            diagnostics.add_measurement(path, || count as f64);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn count_draw_calls(
+    opaque: Res<ViewBinnedRenderPhases<Opaque3d>>,
+    alpha_mask: Res<ViewBinnedRenderPhases<AlphaMask3d>>,
+    opaque_prepass: Res<ViewBinnedRenderPhases<Opaque3dPrepass>>,
+    alpha_mask_prepass: Res<ViewBinnedRenderPhases<AlphaMask3dPrepass>>,
+    opaque_deferred: Res<ViewBinnedRenderPhases<Opaque3dDeferred>>,
+    alpha_mask_deferred: Res<ViewBinnedRenderPhases<AlphaMask3dDeferred>>,
+    transmissive: Res<ViewSortedRenderPhases<Transmissive3d>>,
+    transparent: Res<ViewSortedRenderPhases<Transparent3d>>,
+    shadow: Res<ViewBinnedRenderPhases<Shadow>>,
+    draw_call_counts: Res<DrawCallCounts>,
+) {
+    let main = binned_draw_calls(&opaque)
+        + binned_draw_calls(&alpha_mask)
+        + binned_draw_calls(&opaque_prepass)
+        + binned_draw_calls(&alpha_mask_prepass)
+        + binned_draw_calls(&opaque_deferred)
+        + binned_draw_calls(&alpha_mask_deferred)
+        + sorted_draw_calls(&transmissive)
+        + sorted_draw_calls(&transparent);
+    draw_call_counts.main.store(main, Ordering::Relaxed);
+    draw_call_counts
+        .shadow
+        .store(binned_draw_calls(&shadow), Ordering::Relaxed);
+}
+
+// Each bin is drawn with a single (indirect) draw on platforms with storage buffers.
+// Unbatchable bins (only skinned meshes) are counted once per bin rather than per entity.
+fn binned_draw_calls<BPI: BinnedPhaseItem>(phases: &ViewBinnedRenderPhases<BPI>) -> usize {
+    phases
+        .values()
+        .map(|phase| {
+            phase.batchable_mesh_keys.len()
+                + phase.unbatchable_mesh_keys.len()
+                + phase.non_mesh_items.len()
+        })
+        .sum()
+}
+
+// Mirrors `SortedRenderPhase::render_range`, items merged into a previous batch are skipped.
+fn sorted_draw_calls<SPI: SortedPhaseItem>(phases: &ViewSortedRenderPhases<SPI>) -> usize {
+    phases
+        .values()
+        .map(|phase| {
+            let mut draws = 0;
+            let mut index = 0;
+            while index < phase.items.len() {
+                let batch_range = phase.items[index].batch_range();
+                if batch_range.is_empty() {
+                    index += 1;
+                } else {
+                    draws += 1;
+                    index += batch_range.len();
+                }
+            }
+            draws
+        })
+        .sum()
+}
@@ -0,0 +1,125 @@
+// Alternatives to `StandardMaterial` for random materials, to measure the cost of material
+// specialization: an `ExtendedMaterial` that only tints the base color, a minimal `Material`
+// with its own shader, and a single material sampling a texture array to avoid bind group switches.
+
+use std::str::FromStr;
+
+use bevy::{
+    pbr::{ExtendedMaterial, MaterialExtension},
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef},
+};
+
+/// Type of material used for random materials.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MaterialType {
+    Standard,
+    /// `StandardMaterial` with a `TintExtension`.
+    Extended,
+    /// `SimpleMaterial`, only the base color and alpha mode are kept.
+    Simple,
+    /// A single `ArrayMaterial` for every mesh instance, base color textures packed in an array.
+    Array,
+}
+
+impl FromStr for MaterialType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(Self::Standard),
+            "extended" => Ok(Self::Extended),
+            "simple" => Ok(Self::Simple),
+            "array" => Ok(Self::Array),
+            _ => Err(format!(
+                "unknown material type `{s}`, expected standard, extended, simple or array"
+            )),
+        }
+    }
+}
+
+pub type TintedMaterial = ExtendedMaterial<StandardMaterial, TintExtension>;
+
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+pub struct TintExtension {
+    // Bindings 0-99 are left for the base material.
+    #[uniform(100)]
+    pub tint: LinearRgba,
+}
+
+impl MaterialExtension for TintExtension {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/tint_extension.wgsl".into()
+    }
+}
+
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+pub struct SimpleMaterial {
+    #[uniform(0)]
+    pub color: LinearRgba,
+    #[uniform(0)]
+    pub alpha_cutoff: f32,
+    #[texture(1)]
+    #[sampler(2)]
+    pub base_color_texture: Option<Handle<Image>>,
+    pub alpha_mode: AlphaMode,
+}
+
+impl From<&StandardMaterial> for SimpleMaterial {
+    fn from(material: &StandardMaterial) -> Self {
+        SimpleMaterial {
+            color: material.base_color.into(),
+            alpha_cutoff: match material.alpha_mode {
+                AlphaMode::Mask(cutoff) => cutoff,
+                _ => 0.0,
+            },
+            base_color_texture: material.base_color_texture.clone(),
+            alpha_mode: material.alpha_mode,
+        }
+    }
+}
+
+impl Material for SimpleMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/simple_material.wgsl".into()
+    }
+
+    /// Discards masked texels in the prepass too, otherwise they would still write depth and cast
+    /// shadows.
+    fn prepass_fragment_shader() -> ShaderRef {
+        "shaders/simple_material_prepass.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+}
+
+/// Picks a layer per mesh instance in the shader, so one material (and bind group) is used for
+/// the whole scene.
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+pub struct ArrayMaterial {
+    #[uniform(0)]
+    pub layer_count: u32,
+    #[texture(1, dimension = "2d_array")]
+    #[sampler(2)]
+    pub base_color_texture: Handle<Image>,
+}
+
+impl Material for ArrayMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/array_material.wgsl".into()
+    }
+}
+
+pub struct CustomMaterialsPlugin;
+
+impl Plugin for CustomMaterialsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            MaterialPlugin::<TintedMaterial>::default(),
+            MaterialPlugin::<SimpleMaterial>::default(),
+            MaterialPlugin::<ArrayMaterial>::default(),
+        ));
+    }
+}
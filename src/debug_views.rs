@@ -0,0 +1,332 @@
+// Debug views, press V to cycle through them.
+// Each view swaps a `DebugMaterial` onto every mesh instance. The original material handles are
+// stashed in a `StashedMaterial<M>` component and restored when the views are turned off.
+
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use bevy::{
+    asset::UntypedAssetId,
+    pbr::{MaterialPipeline, MaterialPipelineKey},
+    prelude::*,
+    render::{
+        mesh::MeshVertexBufferLayoutRef,
+        render_resource::{
+            AsBindGroup, PolygonMode, RenderPipelineDescriptor, ShaderRef,
+            SpecializedMeshPipelineError, WgpuFeatures,
+        },
+        renderer::RenderDevice,
+    },
+};
+
+use crate::{
+    custom_materials::{ArrayMaterial, SimpleMaterial, TintedMaterial},
+    hash_noise,
+    lod::MeshLods,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DebugView {
+    #[default]
+    None,
+    /// Base color and base color texture, without lighting.
+    Albedo,
+    /// World space normals.
+    Normals,
+    /// A random color per unique mesh.
+    MeshId,
+    /// A random color per material.
+    MaterialId,
+    /// Additively blended, brighter where more surfaces are drawn on top of each other.
+    Overdraw,
+    /// Requires `WgpuFeatures::POLYGON_MODE_LINE`, skipped when not supported.
+    Wireframe,
+}
+
+impl DebugView {
+    fn next(self) -> Self {
+        match self {
+            DebugView::None => DebugView::Albedo,
+            DebugView::Albedo => DebugView::Normals,
+            DebugView::Normals => DebugView::MeshId,
+            DebugView::MeshId => DebugView::MaterialId,
+            DebugView::MaterialId => DebugView::Overdraw,
+            DebugView::Overdraw => DebugView::Wireframe,
+            DebugView::Wireframe => DebugView::None,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct DebugViews {
+    pub view: DebugView,
+}
+
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+#[bind_group_data(DebugMaterialKey)]
+pub struct DebugMaterial {
+    #[uniform(0)]
+    pub color: LinearRgba,
+    /// 0: color (and texture), 1: world normals
+    #[uniform(0)]
+    pub mode: u32,
+    #[texture(1)]
+    #[sampler(2)]
+    pub texture: Option<Handle<Image>>,
+    pub alpha_mode: AlphaMode,
+    pub wireframe: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct DebugMaterialKey {
+    wireframe: bool,
+}
+
+impl From<&DebugMaterial> for DebugMaterialKey {
+    fn from(material: &DebugMaterial) -> Self {
+        DebugMaterialKey {
+            wireframe: material.wireframe,
+        }
+    }
+}
+
+impl Material for DebugMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/debug_material.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if key.bind_group_data.wireframe {
+            descriptor.primitive.polygon_mode = PolygonMode::Line;
+        }
+        Ok(())
+    }
+}
+
+/// Materials that can be stashed while a debug view is shown.
+pub trait DebugViewMaterial: Material {
+    /// Base color and base color texture, for the albedo view.
+    fn albedo(&self) -> (LinearRgba, Option<Handle<Image>>);
+}
+
+impl DebugViewMaterial for StandardMaterial {
+    fn albedo(&self) -> (LinearRgba, Option<Handle<Image>>) {
+        (self.base_color.into(), self.base_color_texture.clone())
+    }
+}
+
+impl DebugViewMaterial for TintedMaterial {
+    fn albedo(&self) -> (LinearRgba, Option<Handle<Image>>) {
+        self.base.albedo()
+    }
+}
+
+impl DebugViewMaterial for SimpleMaterial {
+    fn albedo(&self) -> (LinearRgba, Option<Handle<Image>>) {
+        (self.color, self.base_color_texture.clone())
+    }
+}
+
+impl DebugViewMaterial for ArrayMaterial {
+    // The array texture can't be sampled as a regular 2D texture.
+    fn albedo(&self) -> (LinearRgba, Option<Handle<Image>>) {
+        (LinearRgba::WHITE, None)
+    }
+}
+
+/// Original material of a mesh instance while a debug view is shown.
+#[derive(Component)]
+pub struct StashedMaterial<M: Material>(pub Handle<M>);
+
+/// What the debug views need to know about the stashed material, whatever its type.
+#[derive(Component)]
+pub struct StashedMaterialInfo {
+    pub id: UntypedAssetId,
+    pub albedo: LinearRgba,
+    pub texture: Option<Handle<Image>>,
+}
+
+pub struct DebugViewsPlugin;
+
+impl Plugin for DebugViewsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MaterialPlugin::<DebugMaterial>::default())
+            .init_resource::<DebugViews>()
+            .add_systems(
+                Update,
+                (
+                    cycle_debug_view,
+                    (
+                        (
+                            stash_materials::<StandardMaterial>,
+                            stash_materials::<TintedMaterial>,
+                            stash_materials::<SimpleMaterial>,
+                            stash_materials::<ArrayMaterial>,
+                        ),
+                        apply_debug_view,
+                    )
+                        .chain()
+                        .run_if(resource_changed::<DebugViews>),
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn cycle_debug_view(
+    input: Res<ButtonInput<KeyCode>>,
+    mut debug_views: ResMut<DebugViews>,
+    render_device: Option<Res<RenderDevice>>,
+) {
+    if !input.just_pressed(KeyCode::KeyV) {
+        return;
+    }
+    let mut view = debug_views.view.next();
+    let polygon_mode_line = render_device
+        .is_some_and(|device| device.features().contains(WgpuFeatures::POLYGON_MODE_LINE));
+    if view == DebugView::Wireframe && !polygon_mode_line {
+        println!("Wireframe debug view skipped, POLYGON_MODE_LINE is not supported");
+        view = view.next();
+    }
+    debug_views.view = view;
+    println!("Debug view: {:?}", view);
+}
+
+pub fn stash_materials<M: DebugViewMaterial>(
+    mut commands: Commands,
+    debug_views: Res<DebugViews>,
+    materials: Res<Assets<M>>,
+    instances: Query<(Entity, &Handle<M>)>,
+    stashed: Query<(Entity, &StashedMaterial<M>)>,
+) {
+    if debug_views.view == DebugView::None {
+        for (entity, StashedMaterial(material_h)) in &stashed {
+            commands
+                .entity(entity)
+                .remove::<(StashedMaterial<M>, StashedMaterialInfo)>()
+                .insert(material_h.clone());
+        }
+        return;
+    }
+    for (entity, material_h) in &instances {
+        let (albedo, texture) = materials
+            .get(material_h)
+            .map(|material| material.albedo())
+            .unwrap_or((LinearRgba::WHITE, None));
+        commands.entity(entity).remove::<Handle<M>>().insert((
+            StashedMaterial(material_h.clone()),
+            StashedMaterialInfo {
+                id: material_h.id().untyped(),
+                albedo,
+                texture,
+            },
+        ));
+    }
+}
+
+fn id_color(id: impl Hash) -> LinearRgba {
+    let hash = hash_key(id) as u32;
+    LinearRgba::rgb(
+        hash_noise(hash, 0, 0),
+        hash_noise(hash, 0, 1),
+        hash_noise(hash, 0, 2),
+    )
+}
+
+type DebugViewInstance<'a> = (
+    Entity,
+    &'a Handle<Mesh>,
+    Option<&'a MeshLods>,
+    Option<&'a StashedMaterialInfo>,
+);
+
+pub fn apply_debug_view(
+    mut commands: Commands,
+    mut debug_materials: ResMut<Assets<DebugMaterial>>,
+    debug_views: Res<DebugViews>,
+    instances: Query<DebugViewInstance>,
+) {
+    let view = debug_views.view;
+    if view == DebugView::None {
+        for (entity, ..) in &instances {
+            commands.entity(entity).remove::<Handle<DebugMaterial>>();
+        }
+        return;
+    }
+
+    let base = DebugMaterial {
+        color: LinearRgba::WHITE,
+        mode: 0,
+        texture: None,
+        alpha_mode: AlphaMode::Opaque,
+        wireframe: false,
+    };
+    // Debug materials are shared per mesh or material, so instancing is kept.
+    let mut cache: HashMap<u64, Handle<DebugMaterial>> = HashMap::new();
+    let mut debug_material = |key: u64, material: &dyn Fn() -> DebugMaterial| {
+        cache
+            .entry(key)
+            .or_insert_with(|| debug_materials.add(material()))
+            .clone()
+    };
+
+    for (entity, mesh_h, lods, info) in &instances {
+        let material = match view {
+            DebugView::None => unreachable!(),
+            DebugView::Albedo => {
+                let key = info.map_or(0, |info| hash_key(info.id));
+                debug_material(key, &|| DebugMaterial {
+                    color: info.map_or(LinearRgba::WHITE, |info| info.albedo),
+                    texture: info.and_then(|info| info.texture.clone()),
+                    ..base.clone()
+                })
+            }
+            DebugView::Normals => debug_material(0, &|| DebugMaterial {
+                mode: 1,
+                ..base.clone()
+            }),
+            DebugView::MeshId => {
+                // LODs keep the color of the original mesh.
+                let mesh_id = lods.map_or(mesh_h.id(), |lods| lods.0[0].id());
+                debug_material(hash_key(mesh_id), &|| DebugMaterial {
+                    color: id_color(mesh_id),
+                    ..base.clone()
+                })
+            }
+            DebugView::MaterialId => {
+                let material_id = info.map(|info| info.id);
+                debug_material(hash_key(material_id), &|| DebugMaterial {
+                    color: id_color(material_id),
+                    ..base.clone()
+                })
+            }
+            DebugView::Overdraw => debug_material(0, &|| DebugMaterial {
+                color: LinearRgba::rgb(0.05, 0.02, 0.01),
+                alpha_mode: AlphaMode::Add,
+                ..base.clone()
+            }),
+            DebugView::Wireframe => debug_material(0, &|| DebugMaterial {
+                wireframe: true,
+                ..base.clone()
+            }),
+        };
+        commands.entity(entity).insert(material);
+    }
+}
+
+fn hash_key(id: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    hasher.finish()
+}
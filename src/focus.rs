@@ -0,0 +1,74 @@
+// Frames the camera on the whole scene, or on the picked mesh instance, press F.
+// With `--focus-scene` the scene is framed once its bounds are computed.
+
+use bevy::{math::bounding::BoundingVolume, prelude::*};
+
+use crate::{bounds::SceneBounds, camera_controller::CameraController, picking::Picked, Args};
+
+// Extra space around the framed bounds.
+const FOCUS_MARGIN: f32 = 1.1;
+
+pub struct FocusPlugin;
+
+impl Plugin for FocusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, focus_camera);
+    }
+}
+
+fn focus_camera(
+    input: Res<ButtonInput<KeyCode>>,
+    args: Res<Args>,
+    bounds: Option<Res<SceneBounds>>,
+    picked: Res<Picked>,
+    mut camera: Query<(&mut Transform, &mut CameraController, &Projection)>,
+) {
+    let key_pressed = input.just_pressed(KeyCode::KeyF);
+    let Some(bounds) = bounds else {
+        if key_pressed {
+            println!("Scene bounds are not computed yet, nothing to focus");
+        }
+        return;
+    };
+    let scene_loaded = args.focus_scene && bounds.is_added();
+    if !key_pressed && !scene_loaded {
+        return;
+    }
+    let Ok((mut transform, mut controller, projection)) = camera.get_single_mut() else {
+        return;
+    };
+
+    let picked = picked
+        .entity
+        .and_then(|entity| bounds.instances.iter().find(|(e, _)| *e == entity));
+    let aabb = match picked {
+        Some((entity, aabb)) => {
+            println!("Focusing on picked {:?}", entity);
+            *aabb
+        }
+        None => {
+            println!("Focusing on the scene");
+            bounds.total
+        }
+    };
+
+    let center = Vec3::from(aabb.center());
+    let radius = aabb.half_size().length().max(0.01) * FOCUS_MARGIN;
+    let distance = match projection {
+        Projection::Perspective(perspective) => {
+            // Fit the bounding sphere in the narrower of the vertical and horizontal fov.
+            let half_fov = perspective.fov * 0.5;
+            let half_fov = half_fov.min((half_fov.tan() * perspective.aspect_ratio).atan());
+            radius / half_fov.sin()
+        }
+        Projection::Orthographic(_) => radius * 2.0,
+    };
+
+    // Keep the current view direction so the controller doesn't snap.
+    let focused = Transform {
+        translation: center - *transform.forward() * distance,
+        ..*transform
+    };
+    controller.set_transform(&mut transform, focused);
+    controller.orbit_focus = center;
+}
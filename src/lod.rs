@@ -0,0 +1,511 @@
+// Automatic mesh LODs generated at load time with quadric edge collapse simplification
+// (Garland & Heckbert), and a system that swaps `Handle<Mesh>` by screen-space size.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    time::Instant,
+};
+
+use bevy::{
+    math::FloatOrd,
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology, VertexAttributeValues},
+    tasks::ComputeTaskPool,
+};
+
+use crate::{bounds::BoundingSphere, materials::start_texture_generation, SceneLoaded};
+
+// Meshes with fewer triangles than this are not worth simplifying.
+const MIN_LOD_TRIANGLES: usize = 64;
+// Stop generating further levels if a level doesn't remove at least this fraction of triangles.
+const MIN_LOD_REDUCTION: f32 = 0.1;
+
+#[derive(Resource, Clone)]
+pub struct LodSettings {
+    /// Number of LOD levels to generate per unique mesh, each with half the triangles of the previous.
+    pub levels: u32,
+    /// Projected bounding sphere diameter (as a fraction of the viewport height) below which LOD 1 is used.
+    /// Each following level is used at half the size of the previous one.
+    pub screen_size: f32,
+    /// When disabled all instances are switched back to their original mesh.
+    pub enabled: bool,
+}
+
+/// All LOD levels of a mesh instance. Index 0 is the original mesh.
+#[derive(Component, Clone)]
+pub struct MeshLods(pub Vec<Handle<Mesh>>);
+
+pub struct LodPlugin {
+    pub settings: LodSettings,
+}
+
+impl Plugin for LodPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone())
+            .add_systems(
+                Update,
+                (
+                    generate_lods
+                        .after(start_texture_generation)
+                        .run_if(resource_added::<SceneLoaded>),
+                    toggle_lods,
+                ),
+            )
+            .add_systems(PostUpdate, select_lods);
+    }
+}
+
+pub fn generate_lods(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mesh_instances: Query<(Entity, &Handle<Mesh>, &GlobalTransform), Without<MeshLods>>,
+    settings: Res<LodSettings>,
+) {
+    let start = Instant::now();
+
+    let mut instances_by_mesh: HashMap<AssetId<Mesh>, Vec<Entity>> = HashMap::new();
+    for (entity, mesh_h, _) in &mesh_instances {
+        instances_by_mesh
+            .entry(mesh_h.id())
+            .or_default()
+            .push(entity);
+    }
+
+    let sources = instances_by_mesh
+        .keys()
+        .filter_map(|id| meshes.get(*id).map(|mesh| (*id, mesh)))
+        .collect::<Vec<_>>();
+
+    let lod_levels = ComputeTaskPool::get().scope(|scope| {
+        for (id, mesh) in &sources {
+            let levels = settings.levels;
+            scope.spawn(async move { (*id, generate_lod_levels(mesh, levels)) });
+        }
+    });
+
+    let mut lod_mesh_count = 0;
+    let mut lod_triangles = [0; 2];
+    for (id, levels) in lod_levels {
+        if levels.is_empty() {
+            continue;
+        }
+        let original = meshes.get(id).map(triangle_count).unwrap_or_default();
+        lod_triangles[0] += original;
+        lod_triangles[1] += levels.last().map(triangle_count).unwrap_or_default();
+        lod_mesh_count += levels.len();
+
+        // Keep a strong handle to the original so switching every instance to a lower LOD
+        // doesn't unload it.
+        let instances = &instances_by_mesh[&id];
+        let Ok((_, original_h, _)) = mesh_instances.get(instances[0]) else {
+            continue;
+        };
+        // Bounds of the original mesh, LOD levels only remove detail.
+        let Some(aabb) = meshes.get(id).and_then(Mesh::compute_aabb) else {
+            continue;
+        };
+        let mut handles = vec![original_h.clone()];
+        handles.extend(levels.into_iter().map(|mesh| meshes.add(mesh)));
+        for entity in instances {
+            let Ok((_, _, transform)) = mesh_instances.get(*entity) else {
+                continue;
+            };
+            commands.entity(*entity).insert((
+                MeshLods(handles.clone()),
+                BoundingSphere::new(&aabb, transform),
+            ));
+        }
+    }
+
+    println!(
+        "Generated {} LOD meshes for {} unique meshes in {:.2}ms (triangles {} -> {} at the lowest LOD)",
+        lod_mesh_count,
+        instances_by_mesh.len(),
+        start.elapsed().as_secs_f32() * 1000.0,
+        lod_triangles[0],
+        lod_triangles[1],
+    );
+}
+
+fn generate_lod_levels(mesh: &Mesh, levels: u32) -> Vec<Mesh> {
+    let mut lods: Vec<Mesh> = Vec::new();
+    for _ in 0..levels {
+        let previous = lods.last().unwrap_or(mesh);
+        let previous_triangles = triangle_count(previous);
+        if previous_triangles < MIN_LOD_TRIANGLES {
+            break;
+        }
+        let Some(lod) = simplify_mesh(previous, previous_triangles / 2) else {
+            break;
+        };
+        if (triangle_count(&lod) as f32) > previous_triangles as f32 * (1.0 - MIN_LOD_REDUCTION) {
+            break;
+        }
+        lods.push(lod);
+    }
+    lods
+}
+
+pub fn triangle_count(mesh: &Mesh) -> usize {
+    mesh.indices()
+        .map(|indices| indices.len())
+        .unwrap_or_else(|| mesh.count_vertices())
+        / 3
+}
+
+fn toggle_lods(input: Res<ButtonInput<KeyCode>>, mut settings: ResMut<LodSettings>) {
+    if input.just_pressed(KeyCode::KeyL) {
+        settings.enabled = !settings.enabled;
+        println!("LODs enabled: {}", settings.enabled);
+    }
+}
+
+pub fn select_lods(
+    camera: Query<(&GlobalTransform, &Projection), With<Camera3d>>,
+    mut lod_instances: Query<(&BoundingSphere, &MeshLods, &mut Handle<Mesh>)>,
+    settings: Res<LodSettings>,
+) {
+    let Ok((camera_transform, Projection::Perspective(projection))) = camera.get_single() else {
+        return;
+    };
+    let camera_position = camera_transform.translation();
+    let half_fov_tan = (projection.fov * 0.5).tan();
+
+    lod_instances
+        .par_iter_mut()
+        .for_each(|(bounds, lods, mut mesh_h)| {
+            let mut level = 0;
+            if settings.enabled {
+                let distance = bounds.center.distance(camera_position).max(f32::EPSILON);
+                let screen_size = bounds.radius / (distance * half_fov_tan);
+                if screen_size < settings.screen_size {
+                    level = ((settings.screen_size / screen_size).log2().floor() as usize + 1)
+                        .min(lods.0.len() - 1);
+                }
+            }
+            if mesh_h.id() != lods.0[level].id() {
+                *mesh_h = lods.0[level].clone();
+            }
+        });
+}
+
+/// Simplifies a triangle list mesh down to roughly `target_triangles` using quadric error metrics.
+/// Vertices on open edges (including UV and normal seams) are locked so no cracks are introduced.
+/// Returns `None` if the mesh isn't an indexed triangle list with `Float32x3` positions.
+pub fn simplify_mesh(mesh: &Mesh, target_triangles: usize) -> Option<Mesh> {
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return None;
+    }
+    let mut positions = mesh
+        .attribute(Mesh::ATTRIBUTE_POSITION)?
+        .as_float3()?
+        .iter()
+        .map(|p| Vec3::from(*p))
+        .collect::<Vec<_>>();
+    let mut triangles = mesh
+        .indices()?
+        .iter()
+        .collect::<Vec<_>>()
+        .chunks_exact(3)
+        .map(|t| [t[0] as u32, t[1] as u32, t[2] as u32])
+        .collect::<Vec<_>>();
+
+    let vertex_count = positions.len();
+    let mut quadrics = vec![Quadric::default(); vertex_count];
+    let mut vertex_triangles = vec![Vec::new(); vertex_count];
+    let mut edge_use: HashMap<(u32, u32), u32> = HashMap::new();
+    for (t, tri) in triangles.iter().enumerate() {
+        let [a, b, c] = tri.map(|v| positions[v as usize]);
+        let cross = (b - a).cross(c - a);
+        let area = cross.length() * 0.5;
+        if area > 0.0 {
+            let normal = cross.normalize();
+            let plane = Quadric::from_plane(normal, -normal.dot(a), area);
+            for v in tri {
+                quadrics[*v as usize].add(&plane);
+            }
+        }
+        for (i, v) in tri.iter().enumerate() {
+            vertex_triangles[*v as usize].push(t as u32);
+            *edge_use.entry(edge_key(*v, tri[(i + 1) % 3])).or_default() += 1;
+        }
+    }
+
+    let mut locked = vec![false; vertex_count];
+    for ((a, b), count) in &edge_use {
+        if *count == 1 {
+            locked[*a as usize] = true;
+            locked[*b as usize] = true;
+        }
+    }
+
+    let mut version = vec![0u32; vertex_count];
+    let mut removed = vec![false; vertex_count];
+    let mut triangle_alive = vec![true; triangles.len()];
+    let mut alive_triangles = triangles.len();
+
+    // Heap entries carry the vertex versions they were computed for, stale entries are skipped.
+    let mut heap = BinaryHeap::new();
+    for (a, b) in edge_use.keys() {
+        if let Some((cost, _)) = plan_collapse(*a, *b, &positions, &quadrics, &locked) {
+            heap.push(Reverse((FloatOrd(cost), *a, *b, 0, 0)));
+        }
+    }
+
+    while alive_triangles > target_triangles {
+        let Some(Reverse((_, a, b, version_a, version_b))) = heap.pop() else {
+            break;
+        };
+        if removed[a as usize]
+            || removed[b as usize]
+            || version[a as usize] != version_a
+            || version[b as usize] != version_b
+        {
+            continue;
+        }
+        let Some((_, target)) = plan_collapse(a, b, &positions, &quadrics, &locked) else {
+            continue;
+        };
+        // Keep the locked vertex if there is one so open edges stay in place.
+        let (keep, remove) = if locked[b as usize] { (b, a) } else { (a, b) };
+
+        if collapse_flips_triangles(
+            keep,
+            remove,
+            target,
+            &positions,
+            &triangles,
+            &triangle_alive,
+            &vertex_triangles,
+        ) {
+            continue;
+        }
+
+        positions[keep as usize] = target;
+        let removed_quadric = quadrics[remove as usize];
+        quadrics[keep as usize].add(&removed_quadric);
+        removed[remove as usize] = true;
+        version[keep as usize] += 1;
+
+        let moved_triangles = std::mem::take(&mut vertex_triangles[remove as usize]);
+        for t in moved_triangles {
+            if !triangle_alive[t as usize] {
+                continue;
+            }
+            let tri = &mut triangles[t as usize];
+            if tri.contains(&keep) {
+                triangle_alive[t as usize] = false;
+                alive_triangles -= 1;
+            } else {
+                for v in tri.iter_mut() {
+                    if *v == remove {
+                        *v = keep;
+                    }
+                }
+                vertex_triangles[keep as usize].push(t);
+            }
+        }
+        vertex_triangles[keep as usize].retain(|t| triangle_alive[*t as usize]);
+
+        let mut neighbors = vertex_triangles[keep as usize]
+            .iter()
+            .flat_map(|t| triangles[*t as usize])
+            .filter(|v| *v != keep)
+            .collect::<Vec<_>>();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        for n in neighbors {
+            if let Some((cost, _)) = plan_collapse(keep, n, &positions, &quadrics, &locked) {
+                heap.push(Reverse((
+                    FloatOrd(cost),
+                    keep,
+                    n,
+                    version[keep as usize],
+                    version[n as usize],
+                )));
+            }
+        }
+    }
+
+    // Compact the remaining vertices and rebuild the index buffer.
+    let mut remap = vec![u32::MAX; vertex_count];
+    let mut kept_vertices = Vec::new();
+    let mut indices = Vec::with_capacity(alive_triangles * 3);
+    for (tri, alive) in triangles.iter().zip(&triangle_alive) {
+        if !alive {
+            continue;
+        }
+        for v in tri {
+            if remap[*v as usize] == u32::MAX {
+                remap[*v as usize] = kept_vertices.len() as u32;
+                kept_vertices.push(*v);
+            }
+            indices.push(remap[*v as usize]);
+        }
+    }
+
+    let mut lod = mesh.clone();
+    for (_, values) in lod.attributes_mut() {
+        *values = gather_vertices(values, &kept_vertices);
+    }
+    lod.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        kept_vertices
+            .iter()
+            .map(|v| positions[*v as usize].to_array())
+            .collect::<Vec<_>>(),
+    );
+    lod.insert_indices(if kept_vertices.len() <= u16::MAX as usize {
+        Indices::U16(indices.iter().map(|i| *i as u16).collect())
+    } else {
+        Indices::U32(indices)
+    });
+    Some(lod)
+}
+
+fn edge_key(a: u32, b: u32) -> (u32, u32) {
+    (a.min(b), a.max(b))
+}
+
+// Returns the error and resulting position of collapsing the edge a-b.
+fn plan_collapse(
+    a: u32,
+    b: u32,
+    positions: &[Vec3],
+    quadrics: &[Quadric],
+    locked: &[bool],
+) -> Option<(f32, Vec3)> {
+    let (pa, pb) = (positions[a as usize], positions[b as usize]);
+    let mut q = quadrics[a as usize];
+    q.add(&quadrics[b as usize]);
+    let candidates = match (locked[a as usize], locked[b as usize]) {
+        (true, true) => return None,
+        (true, false) => vec![pa],
+        (false, true) => vec![pb],
+        (false, false) => vec![pa, pb, (pa + pb) * 0.5],
+    };
+    candidates
+        .into_iter()
+        .map(|p| (q.error(p), p))
+        .min_by(|x, y| x.0.total_cmp(&y.0))
+}
+
+fn collapse_flips_triangles(
+    keep: u32,
+    remove: u32,
+    target: Vec3,
+    positions: &[Vec3],
+    triangles: &[[u32; 3]],
+    triangle_alive: &[bool],
+    vertex_triangles: &[Vec<u32>],
+) -> bool {
+    let affected = vertex_triangles[keep as usize]
+        .iter()
+        .chain(&vertex_triangles[remove as usize]);
+    for t in affected {
+        let tri = triangles[*t as usize];
+        if !triangle_alive[*t as usize] || (tri.contains(&keep) && tri.contains(&remove)) {
+            continue;
+        }
+        let before = tri.map(|v| positions[v as usize]);
+        let after = tri.map(|v| {
+            if v == keep || v == remove {
+                target
+            } else {
+                positions[v as usize]
+            }
+        });
+        let n0 = (before[1] - before[0]).cross(before[2] - before[0]);
+        let n1 = (after[1] - after[0]).cross(after[2] - after[0]);
+        if n0.dot(n1) <= 0.0 {
+            return true;
+        }
+    }
+    false
+}
+
+// Symmetric 4x4 error quadric, upper triangle stored row-major.
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn from_plane(n: Vec3, d: f32, weight: f32) -> Self {
+        let [a, b, c, d] = [n.x, n.y, n.z, d].map(|x| x as f64);
+        let w = weight as f64;
+        Self(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|x| x * w),
+        )
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (a, b) in self.0.iter_mut().zip(other.0) {
+            *a += b;
+        }
+    }
+
+    fn error(&self, p: Vec3) -> f32 {
+        let q = &self.0;
+        let (x, y, z) = (p.x as f64, p.y as f64, p.z as f64);
+        let e = q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9];
+        e.max(0.0) as f32
+    }
+}
+
+fn gather_vertices(values: &VertexAttributeValues, vertices: &[u32]) -> VertexAttributeValues {
+    fn gather<T: Copy>(values: &[T], vertices: &[u32]) -> Vec<T> {
+        vertices.iter().map(|v| values[*v as usize]).collect()
+    }
+    use VertexAttributeValues::*;
+    match values {
+        Float32(v) => Float32(gather(v, vertices)),
+        Sint32(v) => Sint32(gather(v, vertices)),
+        Uint32(v) => Uint32(gather(v, vertices)),
+        Float32x2(v) => Float32x2(gather(v, vertices)),
+        Sint32x2(v) => Sint32x2(gather(v, vertices)),
+        Uint32x2(v) => Uint32x2(gather(v, vertices)),
+        Float32x3(v) => Float32x3(gather(v, vertices)),
+        Sint32x3(v) => Sint32x3(gather(v, vertices)),
+        Uint32x3(v) => Uint32x3(gather(v, vertices)),
+        Float32x4(v) => Float32x4(gather(v, vertices)),
+        Sint32x4(v) => Sint32x4(gather(v, vertices)),
+        Uint32x4(v) => Uint32x4(gather(v, vertices)),
+        Sint16x2(v) => Sint16x2(gather(v, vertices)),
+        Snorm16x2(v) => Snorm16x2(gather(v, vertices)),
+        Uint16x2(v) => Uint16x2(gather(v, vertices)),
+        Unorm16x2(v) => Unorm16x2(gather(v, vertices)),
+        Sint16x4(v) => Sint16x4(gather(v, vertices)),
+        Snorm16x4(v) => Snorm16x4(gather(v, vertices)),
+        Uint16x4(v) => Uint16x4(gather(v, vertices)),
+        Unorm16x4(v) => Unorm16x4(gather(v, vertices)),
+        Sint8x2(v) => Sint8x2(gather(v, vertices)),
+        Snorm8x2(v) => Snorm8x2(gather(v, vertices)),
+        Uint8x2(v) => Uint8x2(gather(v, vertices)),
+        Unorm8x2(v) => Unorm8x2(gather(v, vertices)),
+        Sint8x4(v) => Sint8x4(gather(v, vertices)),
+        Snorm8x4(v) => Snorm8x4(gather(v, vertices)),
+        Uint8x4(v) => Uint8x4(gather(v, vertices)),
+        Unorm8x4(v) => Unorm8x4(gather(v, vertices)),
+    }
+}
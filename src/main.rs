@@ -3,7 +3,21 @@
 
 use std::{f32::consts::PI, time::Instant};
 
+mod bounds;
 mod camera_controller;
+mod culling;
+mod culling_diagnostics;
+mod custom_materials;
+mod debug_views;
+mod focus;
+mod lod;
+mod materials;
+mod occlusion;
+mod picking;
+mod scene_stats;
+mod textures;
+mod viewpoints;
+mod wireframe;
 
 use argh::FromArgs;
 use bevy::{
@@ -11,20 +25,32 @@ use bevy::{
         bloom::BloomSettings,
         experimental::taa::{TemporalAntiAliasBundle, TemporalAntiAliasPlugin},
     },
-    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    ecs::system::SystemParam,
     pbr::{CascadeShadowConfigBuilder, ScreenSpaceAmbientOcclusionBundle},
     prelude::*,
-    render::{
-        render_resource::{
-            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
-        },
-        texture::{ImageAddressMode, ImageSampler, ImageSamplerDescriptor},
-        view::NoFrustumCulling,
-    },
+    render::view::NoFrustumCulling,
     window::{PresentMode, WindowResolution},
     winit::{UpdateMode, WinitSettings},
 };
+use bounds::{SceneBounds, SceneBoundsPlugin};
 use camera_controller::{CameraController, CameraControllerPlugin};
+use culling::{DistanceCullingPlugin, DistanceCullingSettings, DistanceCullingStats};
+use culling_diagnostics::CullingDiagnosticsPlugin;
+use custom_materials::{CustomMaterialsPlugin, MaterialType};
+use debug_views::DebugViewsPlugin;
+use focus::FocusPlugin;
+use lod::{LodPlugin, LodSettings};
+use materials::{
+    assign_rng_materials, report_scene_materials, start_texture_generation, MaterialStrategy,
+    SceneMaterials, TextureGeneration,
+};
+use occlusion::{OcclusionCullingPlugin, OcclusionCullingSettings, OcclusionCullingStats};
+use picking::PickingPlugin;
+use scene_stats::print_scene_stats;
+use textures::{parse_texture_size, ColorFormat, RoughnessFormat};
+use viewpoints::{BenchmarkViewpoints, ViewpointsPlugin};
+use wireframe::WireframeOverlayPlugin;
 
 use crate::light_consts::lux;
 
@@ -39,7 +65,7 @@ pub struct Args {
     #[argh(switch)]
     minimal: bool,
 
-    /// whether to disable frustum culling.
+    /// whether to disable frustum culling. Press C to toggle.
     #[argh(switch)]
     no_frustum_culling: bool,
 
@@ -47,19 +73,123 @@ pub struct Args {
     #[argh(switch)]
     random_materials: bool,
 
-    /// quantity of unique textures sets to randomly select from. (A texture set being: base_color, roughness)
+    /// quantity of unique textures sets to randomly select from. (A texture set being: base_color, roughness, and normal, occlusion and emissive maps when enabled)
     #[argh(option, default = "0")]
     texture_count: u32,
+
+    /// width and height of random textures, a power of two of at least 4
+    #[argh(option, default = "2048", from_str_fn(parse_texture_size))]
+    texture_size: u32,
+
+    /// vary the size of each random texture set between --texture-size and 1/8 of it
+    #[argh(switch)]
+    texture_size_mix: bool,
+
+    /// format of random base color and emissive textures: bc7 (default), bc1, rgba8 (sampled as linear), rgba8-srgb or rgba16f
+    #[argh(option, default = "ColorFormat::Bc7")]
+    texture_format: ColorFormat,
+
+    /// format of random roughness textures: bc7 (default), bc5 or bc4 (shades as zero roughness, for comparing memory and bandwidth only)
+    #[argh(option, default = "RoughnessFormat::Bc7")]
+    roughness_format: RoughnessFormat,
+
+    /// also generate normal maps (BC5) for random materials, generating mesh tangents as needed
+    #[argh(switch)]
+    normal_maps: bool,
+
+    /// also generate occlusion textures (BC4) for random materials
+    #[argh(switch)]
+    occlusion_maps: bool,
+
+    /// also generate emissive textures (same format as base color) for random materials
+    #[argh(switch)]
+    emissive_maps: bool,
+
+    /// fraction (0.0 to 1.0) of random materials using alpha blending
+    #[argh(option, default = "0.0")]
+    blend_fraction: f32,
+
+    /// fraction (0.0 to 1.0) of random materials using alpha masking, joints in the textures are cut out
+    #[argh(option, default = "0.0")]
+    mask_fraction: f32,
+
+    /// make all imported scene materials opaque and single sided, for comparing against random materials
+    #[argh(switch)]
+    normalize_materials: bool,
+
+    /// type of random materials: standard (default), extended (StandardMaterial with a tint extension) simple (minimal custom shader) or array (a single material sampling a texture array)
+    #[argh(option, default = "MaterialType::Standard")]
+    material_type: MaterialType,
+
+    /// how random materials are shared: one, round-robin, per-mesh (default) or per-instance (breaks instancing)
+    #[argh(option, default = "MaterialStrategy::PerMesh")]
+    material_strategy: MaterialStrategy,
+
+    /// quantity of random materials for the round-robin material strategy
+    #[argh(option, default = "16")]
+    material_count: u32,
+
+    /// quantity of simplified LOD levels to generate per unique mesh. Press L to toggle LODs.
+    #[argh(option, default = "0")]
+    lod_levels: u32,
+
+    /// projected size (fraction of viewport height) below which the first LOD level is used.
+    #[argh(option, default = "0.25")]
+    lod_screen_size: f32,
+
+    /// max draw distance for all mesh instances. 0 to disable.
+    #[argh(option, default = "0.0")]
+    max_draw_distance: f32,
+
+    /// max draw distance as a multiple of each mesh instance's bounding sphere radius. 0 to disable.
+    #[argh(option, default = "0.0")]
+    draw_distance_radius_scale: f32,
+
+    /// quantity of the heaviest meshes listed in the scene statistics of the benchmark results.
+    #[argh(option, default = "10")]
+    top_meshes: usize,
+
+    /// benchmark from viewpoints generated from the scene bounds instead of the hotel_01 camera positions.
+    #[argh(switch)]
+    generated_viewpoints: bool,
+
+    /// quantity of generated exterior viewpoints, around the scene.
+    #[argh(option, default = "4")]
+    exterior_viewpoints: usize,
+
+    /// quantity of generated interior viewpoints, at the most open points inside the scene.
+    #[argh(option, default = "4")]
+    interior_viewpoints: usize,
+
+    /// frame the camera on the whole scene once it has loaded. Press F to frame the scene or the picked mesh.
+    #[argh(switch)]
+    focus_scene: bool,
+
+    /// enable experimental CPU occlusion culling. Press O to toggle.
+    #[argh(switch)]
+    occlusion_culling: bool,
+
+    /// quantity of the largest mesh instances to rasterize as occluders for CPU occlusion culling.
+    #[argh(option, default = "256")]
+    occluder_count: usize,
 }
 
 pub fn main() {
     let args: Args = argh::from_env();
+    // The array material is a single opaque material shared by every mesh instance.
+    if args.material_type == MaterialType::Array
+        && (args.material_strategy != MaterialStrategy::PerMesh
+            || args.blend_fraction > 0.0
+            || args.mask_fraction > 0.0)
+    {
+        eprintln!("--material-type array can't be combined with --material-strategy, --blend-fraction or --mask-fraction");
+        std::process::exit(1);
+    }
 
     let mut app = App::new();
 
     app.insert_resource(args.clone())
         .insert_resource(Msaa::Off)
-        // Using just rgb here for bevy 0.13 compat
         .insert_resource(WinitSettings {
             focused_mode: UpdateMode::Continuous,
             unfocused_mode: UpdateMode::Continuous,
@@ -75,14 +205,57 @@ pub fn main() {
         .add_plugins((
             LogDiagnosticsPlugin::default(),
             FrameTimeDiagnosticsPlugin,
+            CullingDiagnosticsPlugin,
+            CustomMaterialsPlugin,
+            DebugViewsPlugin,
+            WireframeOverlayPlugin,
+            PickingPlugin,
+            SceneBoundsPlugin,
+            ViewpointsPlugin,
+            FocusPlugin,
             CameraControllerPlugin,
             TemporalAntiAliasPlugin,
         ))
         .add_systems(Startup, setup)
-        .add_systems(Update, (assign_rng_materials, input, benchmark));
-    if args.no_frustum_culling {
-        app.add_systems(Update, add_no_frustum_culling);
+        .add_systems(
+            Update,
+            (
+                detect_scene_loaded.run_if(not(resource_exists::<SceneLoaded>)),
+                (report_scene_materials, start_texture_generation)
+                    .chain()
+                    .run_if(resource_added::<SceneLoaded>),
+                assign_rng_materials.run_if(resource_exists::<TextureGeneration>),
+                input,
+                benchmark,
+            ),
+        );
+    if args.max_draw_distance > 0.0 || args.draw_distance_radius_scale > 0.0 {
+        app.add_plugins(DistanceCullingPlugin {
+            settings: DistanceCullingSettings {
+                max_distance: args.max_draw_distance,
+                radius_scale: args.draw_distance_radius_scale,
+            },
+        });
     }
+    if args.occlusion_culling {
+        app.add_plugins(OcclusionCullingPlugin {
+            settings: OcclusionCullingSettings {
+                enabled: true,
+                occluder_count: args.occluder_count,
+            },
+        });
+    }
+    if args.lod_levels > 0 {
+        app.add_plugins(LodPlugin {
+            settings: LodSettings {
+                levels: args.lod_levels,
+                screen_size: args.lod_screen_size,
+                enabled: true,
+            },
+        });
+    }
+    app.insert_resource(FrustumCulling(!args.no_frustum_culling))
+        .add_systems(Update, (add_no_frustum_culling, toggle_frustum_culling));
 
     app.run();
 }
@@ -90,6 +263,10 @@ pub fn main() {
 #[derive(Component)]
 pub struct PostProcScene;
 
+/// Inserted once all meshes and mesh instances of the scene have been loaded.
+#[derive(Resource)]
+pub struct SceneLoaded;
+
 #[derive(Component)]
 pub struct GrifLight;
 
@@ -113,8 +290,7 @@ pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>, args: Res<A
                 0.0,
             )),
             directional_light: DirectionalLight {
-                // Using just rgb here for bevy 0.13 compat
-                color: Color::rgb(1.0, 0.87, 0.78),
+                color: Color::srgb(1.0, 0.87, 0.78),
                 illuminance: lux::FULL_DAYLIGHT,
                 shadows_enabled: !args.minimal,
                 shadow_depth_bias: 0.2,
@@ -167,104 +343,14 @@ pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>, args: Res<A
     }
 }
 
-// Go though each unique mesh and randomly generate a material.
-// Each unique so instances are maintained.
-pub fn assign_rng_materials(
+// TODO figure out a better way to reliably figure out things are done loading
+pub fn detect_scene_loaded(
     mut commands: Commands,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut images: ResMut<Assets<Image>>,
     meshes: Res<Assets<Mesh>>,
-    mesh_instances: Query<(Entity, &Handle<Mesh>)>,
-    args: Res<Args>,
-    mut done: Local<bool>,
+    mesh_instances: Query<(), With<Handle<Mesh>>>,
 ) {
-    // TODO figure out a better way to reliably figure out things are done loading
-    let all_meshes_loaded = meshes.len() == UNIQUE_MESH_QTY;
-    let all_mesh_instances_loaded = mesh_instances.iter().len() == MESH_INSTANCE_QTY;
-
-    if !args.random_materials || *done || !all_meshes_loaded || !all_mesh_instances_loaded {
-        return;
-    }
-
-    let base_color_textures = (0..args.texture_count)
-        .map(|i| {
-            images.add(generate_random_compressed_texture_with_mipmaps(
-                2048, false, i,
-            ))
-        })
-        .collect::<Vec<_>>();
-    let roughness_textures = (0..args.texture_count)
-        .map(|i| {
-            images.add(generate_random_compressed_texture_with_mipmaps(
-                2048,
-                false, // Using bc4 here seems to not work
-                i + 2048,
-            ))
-        })
-        .collect::<Vec<_>>();
-
-    for (i, (mesh_h, _mesh)) in meshes.iter().enumerate() {
-        let mut base_color_texture = None;
-        let mut roughness_texture = None;
-
-        if !base_color_textures.is_empty() {
-            base_color_texture = Some(base_color_textures[i % base_color_textures.len()].clone());
-        }
-        if !roughness_textures.is_empty() {
-            roughness_texture = Some(roughness_textures[i % roughness_textures.len()].clone());
-        }
-
-        let unique_material = materials.add(StandardMaterial {
-            base_color: Color::srgb(
-                hash_noise(i as u32, 0, 0),
-                hash_noise(i as u32, 0, 1),
-                hash_noise(i as u32, 0, 2),
-            ),
-            base_color_texture,
-            metallic_roughness_texture: roughness_texture,
-            ..default()
-        });
-        for (entity, mesh_instance_h) in mesh_instances.iter() {
-            if mesh_instance_h.id() == mesh_h {
-                commands.entity(entity).insert(unique_material.clone());
-            }
-        }
-    }
-
-    *done = true;
-}
-
-fn generate_random_compressed_texture_with_mipmaps(size: u32, bc4: bool, seed: u32) -> Image {
-    let (bytes, mip_count) = calculate_bcn_image_size_with_mips(size, if bc4 { 8 } else { 16 });
-    let data = (0..bytes).map(|i| uhash(i, seed) as u8).collect::<Vec<_>>();
-
-    Image {
-        texture_descriptor: TextureDescriptor {
-            label: None,
-            size: Extent3d {
-                width: size,
-                height: size,
-                ..default()
-            },
-            dimension: TextureDimension::D2,
-            format: if bc4 {
-                TextureFormat::Bc4RUnorm
-            } else {
-                TextureFormat::Bc7RgbaUnormSrgb
-            },
-            mip_level_count: mip_count,
-            sample_count: 1,
-            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
-            view_formats: &[],
-        },
-        sampler: ImageSampler::Descriptor(ImageSamplerDescriptor {
-            address_mode_u: ImageAddressMode::Repeat,
-            address_mode_v: ImageAddressMode::Repeat,
-            ..default()
-        }),
-
-        data,
-        ..Default::default()
+    if meshes.len() == UNIQUE_MESH_QTY && mesh_instances.iter().len() == MESH_INSTANCE_QTY {
+        commands.insert_resource(SceneLoaded);
     }
 }
 
@@ -286,59 +372,112 @@ const CAM_POS_3: Transform = Transform {
     scale: Vec3::ONE,
 };
 
-fn input(input: Res<ButtonInput<KeyCode>>, mut camera: Query<&mut Transform, With<Camera>>) {
-    let Ok(mut transform) = camera.get_single_mut() else {
+fn input(
+    input: Res<ButtonInput<KeyCode>>,
+    mut camera: Query<(&mut Transform, &mut CameraController), With<Camera>>,
+) {
+    let Ok((mut transform, mut controller)) = camera.get_single_mut() else {
         return;
     };
     if input.just_pressed(KeyCode::KeyI) {
         info!("{:?}", transform);
     }
     if input.just_pressed(KeyCode::Digit1) {
-        *transform = CAM_POS_1
+        controller.set_transform(&mut transform, CAM_POS_1);
     }
     if input.just_pressed(KeyCode::Digit2) {
-        *transform = CAM_POS_2
+        controller.set_transform(&mut transform, CAM_POS_2);
     }
     if input.just_pressed(KeyCode::Digit3) {
-        *transform = CAM_POS_3
+        controller.set_transform(&mut transform, CAM_POS_3);
     }
 }
 
+// State of optional plugins, reported by the benchmark when present.
+#[derive(SystemParam)]
+struct BenchmarkPluginState<'w> {
+    lod_settings: Option<Res<'w, LodSettings>>,
+    distance_culling_stats: Option<Res<'w, DistanceCullingStats>>,
+    occlusion_culling_stats: Option<Res<'w, OcclusionCullingStats>>,
+    scene_bounds: Option<Res<'w, SceneBounds>>,
+    viewpoints: Option<Res<'w, BenchmarkViewpoints>>,
+}
+
+#[allow(clippy::too_many_arguments)]
 fn benchmark(
     input: Res<ButtonInput<KeyCode>>,
-    mut camera: Query<&mut Transform, With<Camera>>,
-    materials: Res<Assets<StandardMaterial>>,
+    mut camera: Query<(&mut Transform, &mut CameraController), With<Camera>>,
+    materials: SceneMaterials,
     meshes: Res<Assets<Mesh>>,
-    has_std_mat: Query<&Handle<StandardMaterial>>,
-    has_mesh: Query<&Handle<Mesh>>,
+    images: Res<Assets<Image>>,
+    has_mesh: Query<(&Handle<Mesh>, Option<&Name>)>,
     mut bench_started: Local<Option<Instant>>,
     mut bench_frame: Local<u32>,
     mut count_per_step: Local<u32>,
     time: Res<Time>,
+    plugin_state: BenchmarkPluginState,
+    diagnostics: Res<DiagnosticsStore>,
+    frustum_culling: Res<FrustumCulling>,
+    args: Res<Args>,
 ) {
+    // Generated viewpoints are available once the scene bounds are computed.
+    let viewpoints = match &plugin_state.viewpoints {
+        Some(viewpoints) if args.generated_viewpoints && !viewpoints.0.is_empty() => {
+            viewpoints.0.clone()
+        }
+        _ => vec![CAM_POS_1, CAM_POS_2, CAM_POS_3],
+    };
     if input.just_pressed(KeyCode::KeyB) && bench_started.is_none() {
         *bench_started = Some(Instant::now());
         *bench_frame = 0;
         // Try to render for around 2s or at least 30 frames per step
         *count_per_step = ((2.0 / time.delta_seconds()) as u32).max(30);
         println!(
-            "Starting Benchmark with {} frames per step",
-            *count_per_step
+            "Starting Benchmark with {} frames per step and {} viewpoints",
+            *count_per_step,
+            viewpoints.len()
         );
     }
     if bench_started.is_none() {
         return;
     }
-    let Ok(mut transform) = camera.get_single_mut() else {
+    let Ok((mut transform, mut controller)) = camera.get_single_mut() else {
         return;
     };
-    if *bench_frame == 0 {
-        *transform = CAM_POS_1
-    } else if *bench_frame == *count_per_step {
-        *transform = CAM_POS_2
-    } else if *bench_frame == *count_per_step * 2 {
-        *transform = CAM_POS_3
-    } else if *bench_frame == *count_per_step * 3 {
+    if *bench_frame > 0 && (*bench_frame).is_multiple_of(*count_per_step) {
+        let culling_diagnostics = diagnostics
+            .iter()
+            .filter(|d| d.path().as_str().starts_with("culling/"))
+            .filter_map(|d| Some(format!("{}: {:.0}", d.path(), d.average()?)))
+            .collect::<Vec<_>>();
+        println!(
+            "Step {}: {}",
+            *bench_frame / *count_per_step,
+            culling_diagnostics.join(", ")
+        );
+        if let Some(stats) = &plugin_state.distance_culling_stats {
+            println!(
+                "Step {}: {} of {} mesh instances distance culled",
+                *bench_frame / *count_per_step,
+                stats.culled,
+                stats.total
+            );
+        }
+        if let Some(stats) = &plugin_state.occlusion_culling_stats {
+            println!(
+                "Step {}: {} of {} mesh instances occlusion culled ({} occluder triangles, {:.2}ms cpu)",
+                *bench_frame / *count_per_step,
+                stats.culled,
+                stats.tested,
+                stats.occluder_triangles,
+                stats.cpu_time_ms,
+            );
+        }
+    }
+    let step = (*bench_frame / *count_per_step) as usize;
+    if (*bench_frame).is_multiple_of(*count_per_step) && step < viewpoints.len() {
+        controller.set_transform(&mut transform, viewpoints[step]);
+    } else if *bench_frame == *count_per_step * viewpoints.len() as u32 {
         let elapsed = bench_started.unwrap().elapsed().as_secs_f32();
         println!(
             "Benchmark avg cpu frame time: {:.2}ms",
@@ -349,24 +488,74 @@ fn benchmark(
             meshes.len(),
             has_mesh.iter().len(),
             materials.len(),
-            has_std_mat.iter().len(),
+            materials.instance_count(),
+        );
+        print_scene_stats(
+            &meshes,
+            &images,
+            has_mesh.iter(),
+            plugin_state.scene_bounds.as_deref(),
+            args.top_meshes,
         );
+        materials.print_report();
+        println!("Frustum culling: {}", frustum_culling.0);
+        if args.random_materials {
+            println!(
+                "Material strategy: {:?}, type: {:?}",
+                args.material_strategy, args.material_type
+            );
+        }
+        if let Some(lod_settings) = &plugin_state.lod_settings {
+            println!(
+                "LODs: {} levels, enabled: {}",
+                lod_settings.levels, lod_settings.enabled
+            );
+        }
         *bench_started = None;
         *bench_frame = 0;
-        *transform = CAM_POS_1;
+        controller.set_transform(&mut transform, viewpoints[0]);
     }
     *bench_frame += 1;
 }
 
+/// Whether frustum culling is enabled for mesh instances.
+#[derive(Resource)]
+pub struct FrustumCulling(pub bool);
+
+// Only newly spawned mesh instances are handled here, toggle_frustum_culling handles the rest.
 pub fn add_no_frustum_culling(
     mut commands: Commands,
-    convert_query: Query<Entity, (Without<NoFrustumCulling>, With<Handle<StandardMaterial>>)>,
+    frustum_culling: Res<FrustumCulling>,
+    convert_query: Query<Entity, Added<Handle<Mesh>>>,
 ) {
+    if frustum_culling.0 {
+        return;
+    }
     for entity in convert_query.iter() {
         commands.entity(entity).insert(NoFrustumCulling);
     }
 }
 
+pub fn toggle_frustum_culling(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    mut frustum_culling: ResMut<FrustumCulling>,
+    mesh_instances: Query<Entity, With<Handle<Mesh>>>,
+) {
+    if !input.just_pressed(KeyCode::KeyC) {
+        return;
+    }
+    frustum_culling.0 = !frustum_culling.0;
+    for entity in mesh_instances.iter() {
+        if frustum_culling.0 {
+            commands.entity(entity).remove::<NoFrustumCulling>();
+        } else {
+            commands.entity(entity).insert(NoFrustumCulling);
+        }
+    }
+    println!("Frustum culling enabled: {}", frustum_culling.0);
+}
+
 #[inline(always)]
 pub fn uhash(a: u32, b: u32) -> u32 {
     let mut x = (a.overflowing_mul(1597334673).0) ^ (b.overflowing_mul(3812015801).0);
@@ -389,18 +578,3 @@ pub fn hash_noise(x: u32, y: u32, z: u32) -> f32 {
     let urnd = uhash(x, (y << 11) + z);
     unormf(urnd)
 }
-
-// BC7 block is 16 bytes, BC4 block is 8 bytes
-fn calculate_bcn_image_size_with_mips(size: u32, block_size: u32) -> (u32, u32) {
-    let mut total_size = 0;
-    let mut mip_size = size;
-    let mut mip_count = 0;
-    while mip_size > 4 {
-        mip_count += 1;
-        let num_blocks = mip_size / 4; // Round up
-        let mip_level_size = num_blocks * num_blocks * block_size;
-        total_size += mip_level_size;
-        mip_size = (mip_size / 2).max(1);
-    }
-    (total_size, mip_count.max(1))
-}
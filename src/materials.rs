@@ -0,0 +1,656 @@
+// Randomly generated materials for the scene's meshes.
+// Textures are generated in parallel on the `AsyncComputeTaskPool` once the scene has loaded,
+// materials are assigned when every texture is done so the main schedule keeps running meanwhile.
+
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    time::Instant,
+};
+
+use bevy::{
+    ecs::system::SystemParam,
+    pbr::ExtendedMaterial,
+    prelude::*,
+    render::{
+        render_resource::{Face, TextureFormat},
+        renderer::RenderDevice,
+    },
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use futures_lite::future::{block_on, poll_once};
+
+use crate::{
+    custom_materials::{
+        ArrayMaterial, MaterialType, SimpleMaterial, TintExtension, TintedMaterial,
+    },
+    hash_noise,
+    lod::MeshLods,
+    textures::{
+        generate_procedural_texture_with_mipmaps, stack_texture_array, RoughnessFormat,
+        TextureContent,
+    },
+    uhash, Args,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MaterialStrategy {
+    /// A single material shared by every mesh instance.
+    One,
+    /// `--material-count` materials assigned to unique meshes in turn.
+    RoundRobin,
+    /// One material per unique mesh, instances of a mesh share its material.
+    PerMesh,
+    /// One material per mesh instance, this breaks instancing.
+    PerInstance,
+}
+
+impl FromStr for MaterialStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "one" => Ok(Self::One),
+            "round-robin" => Ok(Self::RoundRobin),
+            "per-mesh" => Ok(Self::PerMesh),
+            "per-instance" => Ok(Self::PerInstance),
+            _ => Err(format!(
+                "unknown material strategy `{s}`, expected one, round-robin, per-mesh or per-instance"
+            )),
+        }
+    }
+}
+
+pub fn alpha_mode_name(alpha_mode: AlphaMode) -> &'static str {
+    match alpha_mode {
+        AlphaMode::Opaque => "Opaque",
+        AlphaMode::Mask(_) => "Mask",
+        AlphaMode::Blend => "Blend",
+        AlphaMode::Premultiplied => "Premultiplied",
+        AlphaMode::AlphaToCoverage => "AlphaToCoverage",
+        AlphaMode::Add => "Add",
+        AlphaMode::Multiply => "Multiply",
+    }
+}
+
+/// Materials that can be included in the material report.
+pub trait ReportedMaterial: Material {
+    const NAME: &'static str;
+    /// Base color, metallic roughness, normal, occlusion and emissive textures.
+    fn textures(&self) -> [Option<&Handle<Image>>; 5];
+    fn double_sided(&self) -> bool;
+    fn unlit(&self) -> bool;
+}
+
+impl ReportedMaterial for StandardMaterial {
+    const NAME: &'static str = "StandardMaterial";
+    fn textures(&self) -> [Option<&Handle<Image>>; 5] {
+        [
+            self.base_color_texture.as_ref(),
+            self.metallic_roughness_texture.as_ref(),
+            self.normal_map_texture.as_ref(),
+            self.occlusion_texture.as_ref(),
+            self.emissive_texture.as_ref(),
+        ]
+    }
+    fn double_sided(&self) -> bool {
+        self.double_sided
+    }
+    fn unlit(&self) -> bool {
+        self.unlit
+    }
+}
+
+impl ReportedMaterial for TintedMaterial {
+    const NAME: &'static str = "TintedMaterial";
+    fn textures(&self) -> [Option<&Handle<Image>>; 5] {
+        self.base.textures()
+    }
+    fn double_sided(&self) -> bool {
+        self.base.double_sided
+    }
+    fn unlit(&self) -> bool {
+        self.base.unlit
+    }
+}
+
+impl ReportedMaterial for SimpleMaterial {
+    const NAME: &'static str = "SimpleMaterial";
+    fn textures(&self) -> [Option<&Handle<Image>>; 5] {
+        [self.base_color_texture.as_ref(), None, None, None, None]
+    }
+    fn double_sided(&self) -> bool {
+        false
+    }
+    fn unlit(&self) -> bool {
+        false
+    }
+}
+
+impl ReportedMaterial for ArrayMaterial {
+    const NAME: &'static str = "ArrayMaterial";
+    fn textures(&self) -> [Option<&Handle<Image>>; 5] {
+        [Some(&self.base_color_texture), None, None, None, None]
+    }
+    fn double_sided(&self) -> bool {
+        false
+    }
+    fn unlit(&self) -> bool {
+        false
+    }
+}
+
+type MaterialHandles = (
+    Option<&'static Handle<StandardMaterial>>,
+    Option<&'static Handle<TintedMaterial>>,
+    Option<&'static Handle<SimpleMaterial>>,
+    Option<&'static Handle<ArrayMaterial>>,
+);
+
+/// Every material type the scene's mesh instances can use, for the benchmark report.
+#[derive(SystemParam)]
+pub struct SceneMaterials<'w, 's> {
+    standard: Res<'w, Assets<StandardMaterial>>,
+    tinted: Res<'w, Assets<TintedMaterial>>,
+    simple: Res<'w, Assets<SimpleMaterial>>,
+    array: Res<'w, Assets<ArrayMaterial>>,
+    instances: Query<'w, 's, MaterialHandles, With<Handle<Mesh>>>,
+}
+
+impl SceneMaterials<'_, '_> {
+    /// Materials of every type.
+    pub fn len(&self) -> usize {
+        self.standard.len() + self.tinted.len() + self.simple.len() + self.array.len()
+    }
+
+    /// Mesh instances that have a material of any type.
+    pub fn instance_count(&self) -> usize {
+        self.instances
+            .iter()
+            .filter(|(standard, tinted, simple, array)| {
+                standard.is_some() || tinted.is_some() || simple.is_some() || array.is_some()
+            })
+            .count()
+    }
+
+    /// Prints the material report for each material type used by mesh instances.
+    pub fn print_report(&self) {
+        let instances = || self.instances.iter();
+        if instances().any(|(standard, _, _, _)| standard.is_some()) {
+            print_material_report(&self.standard, instances().filter_map(|(m, _, _, _)| m));
+        }
+        if instances().any(|(_, tinted, _, _)| tinted.is_some()) {
+            print_material_report(&self.tinted, instances().filter_map(|(_, m, _, _)| m));
+        }
+        if instances().any(|(_, _, simple, _)| simple.is_some()) {
+            print_material_report(&self.simple, instances().filter_map(|(_, _, m, _)| m));
+        }
+        if instances().any(|(_, _, _, array)| array.is_some()) {
+            print_material_report(&self.array, instances().filter_map(|(_, _, _, m)| m));
+        }
+    }
+}
+
+/// Prints the materials used by mesh instances: texture usage, alpha modes and double sided flags.
+pub fn print_material_report<'a, M: ReportedMaterial>(
+    materials: &Assets<M>,
+    instances: impl Iterator<Item = &'a Handle<M>>,
+) {
+    let mut instance_counts: HashMap<AssetId<M>, usize> = HashMap::new();
+    for material_h in instances {
+        *instance_counts.entry(material_h.id()).or_default() += 1;
+    }
+    let used = instance_counts
+        .iter()
+        .filter_map(|(id, count)| Some((materials.get(*id)?, *count)))
+        .collect::<Vec<_>>();
+
+    let mut textures = [0; 5];
+    let mut images = HashSet::new();
+    // (name, materials, instances), in order of first appearance.
+    let mut alpha_modes: Vec<(&str, usize, usize)> = Vec::new();
+    let mut double_sided = (0, 0);
+    let mut unlit = 0;
+    for (material, count) in &used {
+        for (used, texture) in textures.iter_mut().zip(material.textures()) {
+            if let Some(texture) = texture {
+                *used += 1;
+                images.insert(texture.id());
+            }
+        }
+        let name = alpha_mode_name(material.alpha_mode());
+        match alpha_modes.iter_mut().find(|(n, ..)| *n == name) {
+            Some((_, materials, instances)) => {
+                *materials += 1;
+                *instances += count;
+            }
+            None => alpha_modes.push((name, 1, *count)),
+        }
+        if material.double_sided() {
+            double_sided.0 += 1;
+            double_sided.1 += count;
+        }
+        if material.unlit() {
+            unlit += 1;
+        }
+    }
+
+    println!(
+        "Materials ({}): {} used by {} mesh instances",
+        M::NAME,
+        used.len(),
+        used.iter().map(|(_, count)| count).sum::<usize>()
+    );
+    println!(
+        "Material textures: base_color {}, metallic_roughness {}, normal {}, occlusion {}, emissive {} ({} unique images)",
+        textures[0],
+        textures[1],
+        textures[2],
+        textures[3],
+        textures[4],
+        images.len()
+    );
+    for (name, materials, instances) in alpha_modes {
+        println!(
+            "Alpha mode {}: {} materials, {} mesh instances",
+            name, materials, instances
+        );
+    }
+    println!(
+        "Double sided: {} materials, {} mesh instances. Unlit: {} materials",
+        double_sided.0, double_sided.1, unlit
+    );
+}
+
+// Runs once the scene has loaded, before random materials replace the imported ones.
+pub fn report_scene_materials(
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    instances: Query<&Handle<StandardMaterial>>,
+    args: Res<Args>,
+) {
+    println!("Imported scene materials:");
+    print_material_report(&materials, instances.iter());
+
+    if args.normalize_materials {
+        let ids = instances.iter().map(|h| h.id()).collect::<HashSet<_>>();
+        let mut normalized = 0;
+        for id in ids {
+            let Some(material) = materials.get_mut(id) else {
+                continue;
+            };
+            if material.alpha_mode != AlphaMode::Opaque || material.double_sided {
+                normalized += 1;
+            }
+            material.alpha_mode = AlphaMode::Opaque;
+            material.double_sided = false;
+            material.cull_mode = Some(Face::Back);
+        }
+        println!(
+            "Normalized {} materials to opaque and single sided",
+            normalized
+        );
+    }
+}
+
+// A random material of the type selected with `--material-type`.
+#[derive(Clone)]
+enum RandomMaterial {
+    Standard(Handle<StandardMaterial>),
+    Tinted(Handle<TintedMaterial>),
+    Simple(Handle<SimpleMaterial>),
+}
+
+/// Textures for random materials that are still being generated.
+#[derive(Resource)]
+pub struct TextureGeneration {
+    tasks: Vec<(TextureContent, u32, Task<Image>)>,
+    textures: Vec<(TextureContent, u32, Handle<Image>)>,
+    total: usize,
+    start: Instant,
+}
+
+impl TextureGeneration {
+    // Handles of one kind of texture, ordered by texture set index.
+    fn textures(&self, content: TextureContent) -> Vec<Handle<Image>> {
+        let mut textures = self
+            .textures
+            .iter()
+            .filter(|(c, ..)| *c == content)
+            .map(|(_, i, handle)| (*i, handle.clone()))
+            .collect::<Vec<_>>();
+        textures.sort_by_key(|(i, _)| *i);
+        textures.into_iter().map(|(_, handle)| handle).collect()
+    }
+}
+
+// Generates mesh tangents if needed and starts generating textures in the background.
+pub fn start_texture_generation(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    args: Res<Args>,
+) {
+    if !args.random_materials {
+        return;
+    }
+
+    if args.roughness_format == RoughnessFormat::Bc4 && args.texture_count > 0 {
+        warn!("StandardMaterial samples roughness from the green channel, BC4 roughness textures will shade as zero roughness");
+    }
+
+    if args.mask_fraction > 0.0 && args.texture_count == 0 {
+        warn!("Masked materials are cut out by base color texture alpha, with --texture-count 0 they will render opaque");
+    }
+
+    // Normal maps need tangents, generate them for any mesh that has what's required.
+    if args.normal_maps && args.texture_count > 0 {
+        let start = Instant::now();
+        let mut generated = 0;
+        for (_, mesh) in meshes.iter_mut() {
+            if mesh.contains_attribute(Mesh::ATTRIBUTE_UV_0)
+                && mesh.contains_attribute(Mesh::ATTRIBUTE_NORMAL)
+                && !mesh.contains_attribute(Mesh::ATTRIBUTE_TANGENT)
+                && mesh.generate_tangents().is_ok()
+            {
+                generated += 1;
+            }
+        }
+        println!(
+            "Generated tangents for {} meshes in {:.2}ms",
+            generated,
+            start.elapsed().as_secs_f32() * 1000.0
+        );
+    }
+
+    // The array material only samples base color, in layers that must all be the same size.
+    let array = args.material_type == MaterialType::Array;
+    if array && args.texture_size_mix {
+        warn!("--texture-size-mix is ignored with the array material type");
+    }
+    let kinds = [
+        (
+            true,
+            args.texture_format.texture_format(),
+            TextureContent::BaseColor,
+            0,
+        ),
+        (
+            !array,
+            args.roughness_format.texture_format(),
+            TextureContent::MetallicRoughness,
+            2048,
+        ),
+        (
+            args.normal_maps && !array,
+            TextureFormat::Bc5RgUnorm,
+            TextureContent::Normal,
+            0,
+        ),
+        (
+            args.occlusion_maps && !array,
+            TextureFormat::Bc4RUnorm,
+            TextureContent::Occlusion,
+            0,
+        ),
+        (
+            args.emissive_maps && !array,
+            args.texture_format.texture_format(),
+            TextureContent::Emissive,
+            0,
+        ),
+    ];
+
+    let pool = AsyncComputeTaskPool::get();
+    let mut tasks = Vec::new();
+    for (enabled, format, content, seed_offset) in kinds {
+        if !enabled {
+            continue;
+        }
+        for i in 0..args.texture_count {
+            // Sizes only depend on the index so every texture in a set matches.
+            let size = if args.texture_size_mix && !array {
+                (args.texture_size >> (uhash(i, 7) % 4)).max(4)
+            } else {
+                args.texture_size
+            };
+            let task = pool.spawn(async move {
+                generate_procedural_texture_with_mipmaps(size, format, content, i + seed_offset)
+            });
+            tasks.push((content, i, task));
+        }
+    }
+
+    if !tasks.is_empty() {
+        println!("Generating {} textures", tasks.len());
+    }
+    commands.insert_resource(TextureGeneration {
+        total: tasks.len(),
+        tasks,
+        textures: Vec::new(),
+        start: Instant::now(),
+    });
+}
+
+// Go though each unique mesh and randomly generate materials according to the material strategy,
+// once all textures are generated.
+// Unless the strategy is per-instance, instances of a mesh share a material so instancing is maintained.
+#[allow(clippy::too_many_arguments)]
+pub fn assign_rng_materials(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut tinted_materials: ResMut<Assets<TintedMaterial>>,
+    mut simple_materials: ResMut<Assets<SimpleMaterial>>,
+    mut array_materials: ResMut<Assets<ArrayMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    meshes: Res<Assets<Mesh>>,
+    mesh_instances: Query<(Entity, &Handle<Mesh>, Option<&MeshLods>)>,
+    render_device: Res<RenderDevice>,
+    mut generation: ResMut<TextureGeneration>,
+    args: Res<Args>,
+) {
+    let before = generation.tasks.len();
+    let mut finished = Vec::new();
+    generation
+        .tasks
+        .retain_mut(|(content, i, task)| match block_on(poll_once(task)) {
+            Some(image) => {
+                finished.push((*content, *i, images.add(image)));
+                false
+            }
+            None => true,
+        });
+    generation.textures.extend(finished);
+
+    let remaining = generation.tasks.len();
+    if remaining != before {
+        // Report roughly every 10%.
+        let done = |remaining: usize| (generation.total - remaining) * 10 / generation.total;
+        if done(remaining) != done(before) {
+            println!(
+                "Generated {}/{} textures ({:.2}s)",
+                generation.total - remaining,
+                generation.total,
+                generation.start.elapsed().as_secs_f32()
+            );
+        }
+    }
+    if remaining > 0 {
+        return;
+    }
+    commands.remove_resource::<TextureGeneration>();
+
+    let texture_time = generation.start.elapsed();
+    let texture_bytes = generation
+        .textures
+        .iter()
+        .filter_map(|(_, _, h)| images.get(h))
+        .map(|image| image.data.len())
+        .sum::<usize>();
+    let base_color_textures = generation.textures(TextureContent::BaseColor);
+    let roughness_textures = generation.textures(TextureContent::MetallicRoughness);
+    let normal_textures = generation.textures(TextureContent::Normal);
+    let occlusion_textures = generation.textures(TextureContent::Occlusion);
+    let emissive_textures = generation.textures(TextureContent::Emissive);
+
+    let start = Instant::now();
+    if args.material_type == MaterialType::Array {
+        if base_color_textures.is_empty() {
+            warn!("The array material type needs --texture-count greater than 0");
+            return;
+        }
+        let max_layers = render_device.limits().max_texture_array_layers as usize;
+        if base_color_textures.len() > max_layers {
+            warn!(
+                "The array material is limited to {} layers by the device, {} textures are unused",
+                max_layers,
+                base_color_textures.len() - max_layers
+            );
+        }
+        let layers = base_color_textures
+            .iter()
+            .take(max_layers)
+            .filter_map(|h| images.get(h))
+            .collect::<Vec<_>>();
+        let layer_count = layers.len() as u32;
+        let array_texture = stack_texture_array(&layers);
+        // The layers would otherwise also be uploaded separately.
+        for h in &base_color_textures {
+            images.remove(h);
+        }
+        let material = array_materials.add(ArrayMaterial {
+            layer_count,
+            base_color_texture: images.add(array_texture),
+        });
+        for (entity, ..) in &mesh_instances {
+            commands
+                .entity(entity)
+                .remove::<Handle<StandardMaterial>>()
+                .insert(material.clone());
+        }
+        println!(
+            "Assigned an array material with {} layers to {} mesh instances in {:.2}ms (texture generation {:.2}ms, {:.1}MiB, color {:?})",
+            layer_count,
+            mesh_instances.iter().len(),
+            start.elapsed().as_secs_f32() * 1000.0,
+            texture_time.as_secs_f32() * 1000.0,
+            texture_bytes as f32 / (1024.0 * 1024.0),
+            args.texture_format,
+        );
+        return;
+    }
+    // Instances may have been switched to a LOD mesh by now, group them by their original mesh.
+    let mut instances_by_mesh: HashMap<AssetId<Mesh>, Vec<Entity>> = HashMap::new();
+    for (entity, mesh_h, lods) in mesh_instances.iter() {
+        let mesh_id = lods.map_or(mesh_h.id(), |lods| lods.0[0].id());
+        instances_by_mesh.entry(mesh_id).or_default().push(entity);
+    }
+
+    // Materials are created on first use of each slot.
+    let mut slot_materials: HashMap<u32, RandomMaterial> = HashMap::new();
+    let mut instance_index = 0;
+    let mut material_instances = Vec::with_capacity(mesh_instances.iter().len());
+    let instanced_meshes = meshes
+        .ids()
+        .filter_map(|id| instances_by_mesh.get(&id))
+        .enumerate();
+    for (i, instances) in instanced_meshes {
+        for entity in instances {
+            let slot = match args.material_strategy {
+                MaterialStrategy::One => 0,
+                MaterialStrategy::RoundRobin => i as u32 % args.material_count.max(1),
+                MaterialStrategy::PerMesh => i as u32,
+                MaterialStrategy::PerInstance => instance_index,
+            };
+            instance_index += 1;
+
+            let material = slot_materials.entry(slot).or_insert_with(|| {
+                let pick = |textures: &Vec<Handle<Image>>| {
+                    (!textures.is_empty()).then(|| textures[slot as usize % textures.len()].clone())
+                };
+                let emissive_texture = pick(&emissive_textures);
+                let alpha = hash_noise(slot, 0, 3);
+                let (alpha_mode, base_alpha) = if alpha < args.blend_fraction {
+                    (AlphaMode::Blend, 0.5)
+                } else if alpha < args.blend_fraction + args.mask_fraction {
+                    (AlphaMode::Mask(0.5), 1.0)
+                } else {
+                    (AlphaMode::Opaque, 1.0)
+                };
+
+                let material = StandardMaterial {
+                    base_color: Color::srgba(
+                        hash_noise(slot, 0, 0),
+                        hash_noise(slot, 0, 1),
+                        hash_noise(slot, 0, 2),
+                        base_alpha,
+                    ),
+                    alpha_mode,
+                    base_color_texture: pick(&base_color_textures),
+                    metallic_roughness_texture: pick(&roughness_textures),
+                    normal_map_texture: pick(&normal_textures),
+                    occlusion_texture: pick(&occlusion_textures),
+                    // The texture is multiplied by emissive, which is black by default.
+                    emissive: if emissive_texture.is_some() {
+                        LinearRgba::gray(2000.0)
+                    } else {
+                        LinearRgba::BLACK
+                    },
+                    emissive_texture,
+                    ..default()
+                };
+                match args.material_type {
+                    MaterialType::Standard => RandomMaterial::Standard(materials.add(material)),
+                    MaterialType::Extended => {
+                        RandomMaterial::Tinted(tinted_materials.add(ExtendedMaterial {
+                            base: material,
+                            extension: TintExtension {
+                                tint: LinearRgba::rgb(
+                                    0.5 + hash_noise(slot, 1, 0) * 0.5,
+                                    0.5 + hash_noise(slot, 1, 1) * 0.5,
+                                    0.5 + hash_noise(slot, 1, 2) * 0.5,
+                                ),
+                            },
+                        }))
+                    }
+                    MaterialType::Simple => RandomMaterial::Simple(
+                        simple_materials.add(SimpleMaterial::from(&material)),
+                    ),
+                    MaterialType::Array => unreachable!("the array material is assigned above"),
+                }
+            });
+            material_instances.push((*entity, material.clone()));
+        }
+    }
+    println!(
+        "Assigned {} random {:?} materials ({:?}) to {} mesh instances in {:.2}ms (texture generation {:.2}ms, {:.1}MiB, color {:?}, roughness {:?})",
+        slot_materials.len(),
+        args.material_type,
+        args.material_strategy,
+        material_instances.len(),
+        start.elapsed().as_secs_f32() * 1000.0,
+        texture_time.as_secs_f32() * 1000.0,
+        texture_bytes as f32 / (1024.0 * 1024.0),
+        args.texture_format,
+        args.roughness_format,
+    );
+
+    let mut standard_instances = Vec::new();
+    for (entity, material) in material_instances {
+        // Custom materials replace the imported StandardMaterial, otherwise both would be drawn.
+        match material {
+            RandomMaterial::Standard(material) => standard_instances.push((entity, material)),
+            RandomMaterial::Tinted(material) => {
+                commands
+                    .entity(entity)
+                    .remove::<Handle<StandardMaterial>>()
+                    .insert(material);
+            }
+            RandomMaterial::Simple(material) => {
+                commands
+                    .entity(entity)
+                    .remove::<Handle<StandardMaterial>>()
+                    .insert(material);
+            }
+        }
+    }
+    commands.insert_or_spawn_batch(standard_instances);
+}
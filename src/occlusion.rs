@@ -0,0 +1,303 @@
+// Experimental CPU occlusion culling.
+// The largest mesh instances are rasterized into a low-res depth buffer each frame, then every
+// other visible instance's Aabb is tested against a hierarchical-Z (max depth) pyramid built from it.
+// Occluded instances have their `ViewVisibility` cleared before extraction, after the light
+// visibility systems, so they are not rendered in any view, including shadows. Occluded shadow
+// casters stop casting shadows, which can show up as missing shadows on visible surfaces.
+
+use std::{
+    collections::HashSet,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use bevy::{
+    math::bounding::BoundingVolume,
+    pbr::SimulationLightSystems,
+    prelude::*,
+    render::{primitives::Aabb, view::VisibilitySystems},
+};
+
+use crate::bounds::SceneBounds;
+
+const DEPTH_WIDTH: usize = 256;
+const DEPTH_HEIGHT: usize = 144;
+// Occluder meshes with more triangles than this are skipped to keep rasterization cheap.
+const MAX_OCCLUDER_TRIANGLES: usize = 4096;
+
+#[derive(Resource, Clone)]
+pub struct OcclusionCullingSettings {
+    pub enabled: bool,
+    /// Quantity of the largest mesh instances (by bounding sphere radius) used as occluders.
+    pub occluder_count: usize,
+}
+
+#[derive(Resource, Default)]
+pub struct OcclusionCullingStats {
+    /// Mesh instances hidden by occlusion culling this frame.
+    pub culled: usize,
+    /// Mesh instances that were visible after frustum culling and tested this frame.
+    pub tested: usize,
+    pub occluder_triangles: usize,
+    /// Time spent rasterizing occluders and testing instances this frame.
+    pub cpu_time_ms: f32,
+}
+
+/// World space occluder triangles, gathered once the scene has loaded.
+#[derive(Resource, Default)]
+pub struct Occluders {
+    pub entities: HashSet<Entity>,
+    pub triangles: Vec<[Vec3; 3]>,
+}
+
+pub struct OcclusionCullingPlugin {
+    pub settings: OcclusionCullingSettings,
+}
+
+impl Plugin for OcclusionCullingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone())
+            .init_resource::<OcclusionCullingStats>()
+            .add_systems(
+                Update,
+                (
+                    gather_occluders.run_if(
+                        resource_exists::<SceneBounds>.and_then(not(resource_exists::<Occluders>)),
+                    ),
+                    toggle_occlusion_culling,
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                occlusion_cull
+                    .after(VisibilitySystems::CheckVisibility)
+                    // Light visibility also sets `ViewVisibility`, for shadow casters.
+                    .after(SimulationLightSystems::CheckLightVisibility)
+                    .run_if(resource_exists::<Occluders>),
+            );
+    }
+}
+
+fn toggle_occlusion_culling(
+    input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<OcclusionCullingSettings>,
+    mut stats: ResMut<OcclusionCullingStats>,
+) {
+    if input.just_pressed(KeyCode::KeyO) {
+        settings.enabled = !settings.enabled;
+        *stats = default();
+        println!("Occlusion culling enabled: {}", settings.enabled);
+    }
+}
+
+pub fn gather_occluders(
+    mut commands: Commands,
+    meshes: Res<Assets<Mesh>>,
+    instances: Query<(&GlobalTransform, &Handle<Mesh>)>,
+    bounds: Res<SceneBounds>,
+    settings: Res<OcclusionCullingSettings>,
+) {
+    let mut candidates = bounds
+        .instances
+        .iter()
+        .filter_map(|(entity, aabb)| {
+            let (transform, mesh_h) = instances.get(*entity).ok()?;
+            let mesh = meshes.get(mesh_h)?;
+            let indices = mesh.indices()?;
+            if indices.len() / 3 > MAX_OCCLUDER_TRIANGLES {
+                return None;
+            }
+            let radius = aabb.half_size().length();
+            Some((radius, *entity, transform, mesh))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut occluders = Occluders::default();
+    for (_, entity, transform, mesh) in candidates.into_iter().take(settings.occluder_count) {
+        let (Some(positions), Some(indices)) = (
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+                .and_then(|p| p.as_float3()),
+            mesh.indices(),
+        ) else {
+            continue;
+        };
+        let indices = indices.iter().collect::<Vec<_>>();
+        occluders.entities.insert(entity);
+        occluders.triangles.extend(indices.chunks_exact(3).map(|t| {
+            [t[0], t[1], t[2]].map(|i| transform.transform_point(Vec3::from(positions[i])))
+        }));
+    }
+
+    println!(
+        "Occlusion culling: {} occluders with {} triangles",
+        occluders.entities.len(),
+        occluders.triangles.len()
+    );
+    commands.insert_resource(occluders);
+}
+
+pub fn occlusion_cull(
+    camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut instances: Query<
+        (Entity, &GlobalTransform, &Aabb, &mut ViewVisibility),
+        With<Handle<Mesh>>,
+    >,
+    occluders: Res<Occluders>,
+    settings: Res<OcclusionCullingSettings>,
+    mut stats: ResMut<OcclusionCullingStats>,
+) {
+    if !settings.enabled {
+        return;
+    }
+    let Ok((camera, camera_transform)) = camera.get_single() else {
+        return;
+    };
+    let start = Instant::now();
+    let clip_from_world = camera.clip_from_view() * camera_transform.compute_matrix().inverse();
+
+    let mut depth = vec![f32::INFINITY; DEPTH_WIDTH * DEPTH_HEIGHT];
+    for triangle in &occluders.triangles {
+        rasterize_triangle(
+            &mut depth,
+            triangle.map(|p| clip_from_world * p.extend(1.0)),
+        );
+    }
+    let hiz = HiZ::new(depth);
+
+    let tested = AtomicUsize::new(0);
+    let culled = AtomicUsize::new(0);
+    instances
+        .par_iter_mut()
+        .for_each(|(entity, transform, aabb, mut view_visibility)| {
+            if !view_visibility.get() || occluders.entities.contains(&entity) {
+                return;
+            }
+            tested.fetch_add(1, Ordering::Relaxed);
+            if hiz.is_occluded(clip_from_world * transform.compute_matrix(), aabb) {
+                *view_visibility = ViewVisibility::HIDDEN;
+                culled.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+
+    *stats = OcclusionCullingStats {
+        culled: culled.into_inner(),
+        tested: tested.into_inner(),
+        occluder_triangles: occluders.triangles.len(),
+        cpu_time_ms: start.elapsed().as_secs_f32() * 1000.0,
+    };
+}
+
+// Converts clip space to depth buffer pixel coordinates.
+fn to_pixel(clip: Vec4) -> Vec2 {
+    let ndc = clip.truncate().truncate() / clip.w;
+    Vec2::new(
+        (ndc.x * 0.5 + 0.5) * DEPTH_WIDTH as f32,
+        (0.5 - ndc.y * 0.5) * DEPTH_HEIGHT as f32,
+    )
+}
+
+// Writes the farthest vertex depth of the triangle so the buffer stays conservative.
+// Triangles crossing the near plane are skipped rather than clipped.
+fn rasterize_triangle(depth: &mut [f32], clip: [Vec4; 3]) {
+    if clip.iter().any(|c| c.w <= f32::EPSILON || c.z > c.w) {
+        return;
+    }
+    let [a, b, c] = clip.map(to_pixel);
+    let area = (b - a).perp_dot(c - a);
+    if area.abs() < f32::EPSILON {
+        return;
+    }
+    let triangle_depth = clip[0].w.max(clip[1].w).max(clip[2].w);
+
+    let min = a.min(b).min(c).floor().max(Vec2::ZERO);
+    let max = a
+        .max(b)
+        .max(c)
+        .ceil()
+        .min(Vec2::new(DEPTH_WIDTH as f32, DEPTH_HEIGHT as f32));
+    for y in min.y as usize..max.y as usize {
+        for x in min.x as usize..max.x as usize {
+            let p = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+            let w0 = (c - b).perp_dot(p - b) * area.signum();
+            let w1 = (a - c).perp_dot(p - c) * area.signum();
+            let w2 = (b - a).perp_dot(p - a) * area.signum();
+            if w0 >= 0.0 && w1 >= 0.0 && w2 >= 0.0 {
+                let d = &mut depth[y * DEPTH_WIDTH + x];
+                *d = d.min(triangle_depth);
+            }
+        }
+    }
+}
+
+// Max depth pyramid, level 0 is the full resolution depth buffer.
+struct HiZ {
+    levels: Vec<(usize, usize, Vec<f32>)>,
+}
+
+impl HiZ {
+    fn new(depth: Vec<f32>) -> Self {
+        let mut levels = vec![(DEPTH_WIDTH, DEPTH_HEIGHT, depth)];
+        while let Some((w, h, prev)) = levels.last().filter(|(w, h, _)| *w > 1 || *h > 1) {
+            let (nw, nh) = (w.div_ceil(2), h.div_ceil(2));
+            let mut next = vec![0.0f32; nw * nh];
+            for y in 0..nh {
+                for x in 0..nw {
+                    let mut d = 0.0f32;
+                    for (sx, sy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let (px, py) = ((x * 2 + sx).min(w - 1), (y * 2 + sy).min(h - 1));
+                        d = d.max(prev[py * w + px]);
+                    }
+                    next[y * nw + x] = d;
+                }
+            }
+            levels.push((nw, nh, next));
+        }
+        Self { levels }
+    }
+
+    fn is_occluded(&self, clip_from_local: Mat4, aabb: &Aabb) -> bool {
+        let mut min = Vec2::splat(f32::MAX);
+        let mut max = Vec2::splat(f32::MIN);
+        let mut nearest = f32::MAX;
+        for i in 0..8 {
+            let corner = Vec3::from(aabb.center)
+                + Vec3::from(aabb.half_extents)
+                    * Vec3::new(
+                        if i & 1 == 0 { -1.0 } else { 1.0 },
+                        if i & 2 == 0 { -1.0 } else { 1.0 },
+                        if i & 4 == 0 { -1.0 } else { 1.0 },
+                    );
+            let clip = clip_from_local * corner.extend(1.0);
+            // Crossing the near plane, treat as visible.
+            if clip.z > clip.w || clip.w <= f32::EPSILON {
+                return false;
+            }
+            let p = to_pixel(clip);
+            min = min.min(p);
+            max = max.max(p);
+            nearest = nearest.min(clip.w);
+        }
+
+        let screen = Vec2::new(DEPTH_WIDTH as f32, DEPTH_HEIGHT as f32);
+        let min = min.clamp(Vec2::ZERO, screen - 1.0);
+        let max = max.clamp(Vec2::ZERO, screen - 1.0);
+        // Pick the level where the rect covers at most 2x2 texels.
+        let extent = (max - min).max_element().max(1.0);
+        let level = (extent.log2().ceil() as usize).min(self.levels.len() - 1);
+        let (w, h, depth) = &self.levels[level];
+        let (x0, y0) = (min.x as usize >> level, min.y as usize >> level);
+        let (x1, y1) = (
+            (max.x as usize >> level).min(w - 1),
+            (max.y as usize >> level).min(h - 1),
+        );
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                if nearest <= depth[y * w + x] {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,267 @@
+// Ray picking of mesh instances under the cursor, against their Aabbs and then triangles.
+// Right click to pick and inspect a mesh instance, the picked instance's Aabb is highlighted.
+
+use bevy::{
+    prelude::*,
+    render::{mesh::PrimitiveTopology, primitives::Aabb},
+    window::PrimaryWindow,
+};
+
+use crate::{
+    bounds::{mesh_aabb, SceneBounds},
+    lod::triangle_count,
+};
+
+pub type PickableInstances<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static GlobalTransform,
+        Option<&'static Aabb>,
+        &'static Handle<Mesh>,
+    ),
+>;
+
+/// Mesh instance picked with the right mouse button.
+#[derive(Resource, Default)]
+pub struct Picked {
+    pub entity: Option<Entity>,
+}
+
+pub struct PickingPlugin;
+
+impl Plugin for PickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Picked>()
+            .add_systems(Update, (pick_on_right_click, highlight_picked).chain());
+    }
+}
+
+/// World space ray from the camera through the cursor, if the cursor is in the window.
+pub fn cursor_ray(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    camera: &Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+) -> Option<Ray3d> {
+    let cursor = windows.get_single().ok()?.cursor_position()?;
+    let (camera, camera_transform) = camera.get_single().ok()?;
+    camera.viewport_to_world(camera_transform, cursor)
+}
+
+/// Distance along the ray to the entry point of the local space `aabb`, or the ray origin if
+/// it's inside. `direction` doesn't need to be normalized, the result is in units of it.
+pub fn ray_aabb_intersection(origin: Vec3, direction: Vec3, aabb: &Aabb) -> Option<f32> {
+    let min = Vec3::from(aabb.min());
+    let max = Vec3::from(aabb.max());
+    let inv_direction = direction.recip();
+    let t0 = (min - origin) * inv_direction;
+    let t1 = (max - origin) * inv_direction;
+    let near = t0.min(t1).max_element();
+    let far = t0.max(t1).min_element();
+    (near <= far && far >= 0.0).then_some(near.max(0.0))
+}
+
+// Möller–Trumbore, returns the distance in units of `direction`.
+fn ray_triangle_intersection(origin: Vec3, direction: Vec3, [a, b, c]: [Vec3; 3]) -> Option<f32> {
+    let (ab, ac) = (b - a, c - a);
+    let p = direction.cross(ac);
+    let det = ab.dot(p);
+    if det.abs() < f32::EPSILON {
+        return None;
+    }
+    let inv_det = det.recip();
+    let s = origin - a;
+    let u = s.dot(p) * inv_det;
+    let q = s.cross(ab);
+    let v = direction.dot(q) * inv_det;
+    if u < 0.0 || v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = ac.dot(q) * inv_det;
+    (t >= 0.0).then_some(t)
+}
+
+// Nearest triangle hit in local space. Meshes that aren't triangle lists fall back to the Aabb hit.
+fn ray_mesh_intersection(origin: Vec3, direction: Vec3, mesh: &Mesh) -> Option<Option<f32>> {
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return None;
+    }
+    let positions = mesh.attribute(Mesh::ATTRIBUTE_POSITION)?.as_float3()?;
+    let indices = match mesh.indices() {
+        Some(indices) => indices.iter().collect::<Vec<_>>(),
+        None => (0..positions.len()).collect(),
+    };
+    Some(
+        indices
+            .chunks_exact(3)
+            .filter_map(|t| {
+                let triangle = [t[0], t[1], t[2]].map(|i| Vec3::from(positions[i]));
+                ray_triangle_intersection(origin, direction, triangle)
+            })
+            .min_by(|a, b| a.total_cmp(b)),
+    )
+}
+
+/// Nearest mesh instance hit by the ray, tested against triangles of the instances whose Aabb is hit.
+pub fn pick_mesh<'a>(
+    ray: Ray3d,
+    instances: impl Iterator<
+        Item = (
+            Entity,
+            &'a GlobalTransform,
+            Option<&'a Aabb>,
+            &'a Handle<Mesh>,
+        ),
+    >,
+    meshes: &Assets<Mesh>,
+) -> Option<(Entity, f32)> {
+    let mut candidates = instances
+        .filter_map(|(entity, transform, aabb, mesh_h)| {
+            let local_from_world = transform.affine().inverse();
+            let origin = local_from_world.transform_point3(ray.origin);
+            let direction = local_from_world.transform_vector3(*ray.direction);
+            let aabb = mesh_aabb(aabb, mesh_h, meshes)?;
+            let distance = ray_aabb_intersection(origin, direction, &aabb)?;
+            Some((distance, entity, origin, direction, mesh_h))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut nearest: Option<(Entity, f32)> = None;
+    for (aabb_distance, entity, origin, direction, mesh_h) in candidates {
+        // Triangles can't be closer than their Aabb.
+        if nearest.is_some_and(|(_, distance)| distance <= aabb_distance) {
+            break;
+        }
+        let distance = match meshes
+            .get(mesh_h)
+            .and_then(|mesh| ray_mesh_intersection(origin, direction, mesh))
+        {
+            Some(Some(distance)) => distance,
+            Some(None) => continue,
+            None => aabb_distance,
+        };
+        if nearest.is_none_or(|(_, nearest)| distance < nearest) {
+            nearest = Some((entity, distance));
+        }
+    }
+    nearest
+}
+
+/// Like `pick_mesh`, but only tests the instances found along the ray by the scene BVH once it's available.
+pub fn pick_scene_mesh(
+    ray: Ray3d,
+    instances: &PickableInstances,
+    meshes: &Assets<Mesh>,
+    bounds: Option<&SceneBounds>,
+) -> Option<(Entity, f32)> {
+    match bounds {
+        Some(bounds) => pick_mesh(
+            ray,
+            bounds
+                .bvh
+                .ray_candidates(ray)
+                .into_iter()
+                .filter_map(|(entity, _)| instances.get(entity).ok()),
+            meshes,
+        ),
+        None => pick_mesh(ray, instances.iter(), meshes),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn pick_on_right_click(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    instances: PickableInstances,
+    bounds: Option<Res<SceneBounds>>,
+    names: Query<(Option<&Name>, Option<&Parent>)>,
+    std_materials: Query<&Handle<StandardMaterial>>,
+    meshes: Res<Assets<Mesh>>,
+    materials: Res<Assets<StandardMaterial>>,
+    mut picked: ResMut<Picked>,
+) {
+    if !mouse_input.just_pressed(MouseButton::Right) {
+        return;
+    }
+    let Some(ray) = cursor_ray(&windows, &camera) else {
+        return;
+    };
+    let Some((entity, distance)) = pick_scene_mesh(ray, &instances, &meshes, bounds.as_deref())
+    else {
+        picked.entity = None;
+        println!("Picked nothing");
+        return;
+    };
+    picked.entity = Some(entity);
+
+    // glTF primitives are named after their mesh, the parent entity is the node.
+    let (name, parent) = names.get(entity).unwrap_or_default();
+    let node_name = parent
+        .and_then(|parent| names.get(parent.get()).ok())
+        .and_then(|(name, _)| name);
+    println!(
+        "Picked {:?} at distance {:.2}: node {:?}, primitive {:?}",
+        entity,
+        distance,
+        node_name.map(Name::as_str),
+        name.map(Name::as_str)
+    );
+
+    let Ok((_, transform, _, mesh_h)) = instances.get(entity) else {
+        return;
+    };
+    match meshes.get(mesh_h) {
+        Some(mesh) => println!(
+            "  Mesh {:?}: {} vertices, {} triangles",
+            mesh_h.id(),
+            mesh.count_vertices(),
+            triangle_count(mesh)
+        ),
+        None => println!("  Mesh {:?}: not loaded", mesh_h.id()),
+    }
+    match std_materials
+        .get(entity)
+        .ok()
+        .and_then(|material_h| Some((material_h, materials.get(material_h)?)))
+    {
+        Some((material_h, material)) => println!(
+            "  Material {:?}: base color {:?}, alpha mode {:?}, double sided {}, textures: base_color {}, metallic_roughness {}, normal {}",
+            material_h.id(),
+            material.base_color,
+            material.alpha_mode,
+            material.double_sided,
+            material.base_color_texture.is_some(),
+            material.metallic_roughness_texture.is_some(),
+            material.normal_map_texture.is_some(),
+        ),
+        None => println!("  Material: not a StandardMaterial"),
+    }
+    // Printed as a matrix, decomposing it would lose any shear from non-uniform scales.
+    let transform = transform.affine();
+    println!(
+        "  Transform: translation {}, matrix {}",
+        transform.translation, transform.matrix3
+    );
+}
+
+fn highlight_picked(
+    mut gizmos: Gizmos,
+    picked: Res<Picked>,
+    instances: Query<(&GlobalTransform, Option<&Aabb>, &Handle<Mesh>)>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    let Some((transform, aabb)) = picked.entity.and_then(|entity| {
+        let (transform, aabb, mesh_h) = instances.get(entity).ok()?;
+        Some((transform, mesh_aabb(aabb, mesh_h, &meshes)?))
+    }) else {
+        return;
+    };
+    let local = Transform::from_translation(aabb.center.into())
+        .with_scale(Vec3::from(aabb.half_extents) * 2.0);
+    gizmos.cuboid(
+        *transform * GlobalTransform::from(local),
+        Color::srgb(1.0, 0.8, 0.0),
+    );
+}
@@ -0,0 +1,140 @@
+// Scene statistics printed with the benchmark results: geometry totals, index formats, vertex
+// layouts, estimated GPU memory and the heaviest meshes.
+
+use std::{cmp::Reverse, collections::HashMap};
+
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, MeshVertexAttributeId},
+};
+
+use crate::{bounds::SceneBounds, lod::triangle_count};
+
+fn attribute_name(id: MeshVertexAttributeId) -> String {
+    [
+        Mesh::ATTRIBUTE_POSITION,
+        Mesh::ATTRIBUTE_NORMAL,
+        Mesh::ATTRIBUTE_UV_0,
+        Mesh::ATTRIBUTE_UV_1,
+        Mesh::ATTRIBUTE_TANGENT,
+        Mesh::ATTRIBUTE_COLOR,
+        Mesh::ATTRIBUTE_JOINT_INDEX,
+        Mesh::ATTRIBUTE_JOINT_WEIGHT,
+    ]
+    .into_iter()
+    .find(|attribute| attribute.id == id)
+    .map_or_else(|| format!("{id:?}"), |attribute| attribute.name.to_string())
+}
+
+fn mib(bytes: usize) -> f32 {
+    bytes as f32 / (1024.0 * 1024.0)
+}
+
+/// Prints statistics for every mesh and image asset, and for the meshes used by `instances`.
+pub fn print_scene_stats<'a>(
+    meshes: &Assets<Mesh>,
+    images: &Assets<Image>,
+    instances: impl Iterator<Item = (&'a Handle<Mesh>, Option<&'a Name>)>,
+    bounds: Option<&SceneBounds>,
+    top_n: usize,
+) {
+    // Instance count and a name per mesh.
+    let mut mesh_instances: HashMap<AssetId<Mesh>, (usize, Option<&Name>)> = HashMap::new();
+    for (mesh_h, name) in instances {
+        let entry = mesh_instances.entry(mesh_h.id()).or_default();
+        entry.0 += 1;
+        entry.1 = entry.1.or(name);
+    }
+
+    let mut triangles = 0;
+    let mut vertices = 0;
+    let mut vertex_bytes = 0;
+    let mut index_bytes = 0;
+    let mut index_formats: [(&str, usize); 3] = [("u16", 0), ("u32", 0), ("none", 0)];
+    let mut layouts: HashMap<Vec<MeshVertexAttributeId>, usize> = HashMap::new();
+    for (_, mesh) in meshes.iter() {
+        triangles += triangle_count(mesh);
+        vertices += mesh.count_vertices();
+        vertex_bytes += mesh.count_vertices() * mesh.get_vertex_size() as usize;
+        index_bytes += mesh.get_index_buffer_bytes().map_or(0, |bytes| bytes.len());
+        index_formats[match mesh.indices() {
+            Some(Indices::U16(_)) => 0,
+            Some(Indices::U32(_)) => 1,
+            None => 2,
+        }]
+        .1 += 1;
+        let mut layout = mesh.attributes().map(|(id, _)| id).collect::<Vec<_>>();
+        layout.sort_by_key(|id| format!("{id:?}"));
+        *layouts.entry(layout).or_default() += 1;
+    }
+
+    let instanced_triangles = mesh_instances
+        .iter()
+        .filter_map(|(id, (count, _))| Some(triangle_count(meshes.get(*id)?) * count))
+        .sum::<usize>();
+    let instanced_vertices = mesh_instances
+        .iter()
+        .filter_map(|(id, (count, _))| Some(meshes.get(*id)?.count_vertices() * count))
+        .sum::<usize>();
+    let texture_bytes = images
+        .iter()
+        .map(|(_, image)| image.data.len())
+        .sum::<usize>();
+
+    println!(
+        "Scene: {} meshes with {} triangles and {} vertices, {} instances with {} triangles and {} vertices",
+        meshes.len(),
+        triangles,
+        vertices,
+        mesh_instances.values().map(|(count, _)| count).sum::<usize>(),
+        instanced_triangles,
+        instanced_vertices,
+    );
+    if let Some(bounds) = bounds {
+        println!(
+            "Scene bounds: min {}, max {}, size {}",
+            bounds.total.min,
+            bounds.total.max,
+            bounds.total.max - bounds.total.min
+        );
+    }
+    println!(
+        "Index formats: {}",
+        index_formats
+            .iter()
+            .map(|(format, count)| format!("{format} {count}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let mut layouts = layouts.into_iter().collect::<Vec<_>>();
+    layouts.sort_by_key(|(_, count)| Reverse(*count));
+    for (layout, count) in layouts {
+        let names = layout.into_iter().map(attribute_name).collect::<Vec<_>>();
+        println!("Vertex layout [{}]: {} meshes", names.join(", "), count);
+    }
+    println!(
+        "Estimated GPU memory: vertices {:.1}MiB, indices {:.1}MiB, textures {:.1}MiB ({} images)",
+        mib(vertex_bytes),
+        mib(index_bytes),
+        mib(texture_bytes),
+        images.len(),
+    );
+
+    let mut heaviest = mesh_instances
+        .iter()
+        .filter_map(|(id, (count, name))| {
+            Some((triangle_count(meshes.get(*id)?), *count, *name, id))
+        })
+        .collect::<Vec<_>>();
+    heaviest.sort_by_key(|(triangles, ..)| Reverse(*triangles));
+    println!("Top {} meshes by triangle count:", top_n);
+    for (triangles, count, name, id) in heaviest.into_iter().take(top_n) {
+        println!(
+            "  {} triangles, {} instances: {} ({:?})",
+            triangles,
+            count,
+            name.map_or("unnamed", Name::as_str),
+            id
+        );
+    }
+}
@@ -0,0 +1,728 @@
+// Procedural texture generation for random materials.
+// Patterns are generated as RGBA8 with a box filtered mip chain, then block compressed in Rust
+// so the textures look plausible and sample like real content.
+
+use std::str::FromStr;
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+            TextureViewDescriptor, TextureViewDimension,
+        },
+        texture::{ImageAddressMode, ImageSampler, ImageSamplerDescriptor},
+    },
+};
+
+use crate::{hash_noise, uhash};
+
+/// What a generated texture is used for, which decides how its channels are filled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureContent {
+    /// Alpha is 0 in the joints between bricks and tiles.
+    BaseColor,
+    /// Roughness in G and metallic in B, as sampled by `StandardMaterial`.
+    MetallicRoughness,
+    /// Tangent space normal in RG, use a two channel format so `StandardMaterial` reconstructs Z.
+    Normal,
+    /// Ambient occlusion in R.
+    Occlusion,
+    Emissive,
+}
+
+/// Format of generated metallic roughness textures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoughnessFormat {
+    /// RGBA, roughness in G and metallic in B.
+    Bc7,
+    /// Two channels, roughness in G. Metallic reads as 0.
+    Bc5,
+    /// Single channel roughness in R. `StandardMaterial` samples roughness from G, which reads as 0
+    /// with BC4, so this only makes sense for comparing memory and bandwidth.
+    Bc4,
+}
+
+impl RoughnessFormat {
+    pub fn texture_format(self) -> TextureFormat {
+        match self {
+            RoughnessFormat::Bc7 => TextureFormat::Bc7RgbaUnorm,
+            RoughnessFormat::Bc5 => TextureFormat::Bc5RgUnorm,
+            RoughnessFormat::Bc4 => TextureFormat::Bc4RUnorm,
+        }
+    }
+}
+
+impl FromStr for RoughnessFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bc7" => Ok(Self::Bc7),
+            "bc5" => Ok(Self::Bc5),
+            "bc4" => Ok(Self::Bc4),
+            _ => Err(format!(
+                "unknown roughness format `{s}`, expected bc7, bc5 or bc4"
+            )),
+        }
+    }
+}
+
+/// Format of generated base color and emissive textures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorFormat {
+    Bc7,
+    Bc1,
+    /// Uncompressed, sampled as linear so colors look washed out. For comparing bandwidth only.
+    Rgba8,
+    Rgba8Srgb,
+    /// Uncompressed half float, 8 bytes per texel.
+    Rgba16f,
+}
+
+impl ColorFormat {
+    pub fn texture_format(self) -> TextureFormat {
+        match self {
+            ColorFormat::Bc7 => TextureFormat::Bc7RgbaUnormSrgb,
+            ColorFormat::Bc1 => TextureFormat::Bc1RgbaUnormSrgb,
+            ColorFormat::Rgba8 => TextureFormat::Rgba8Unorm,
+            ColorFormat::Rgba8Srgb => TextureFormat::Rgba8UnormSrgb,
+            ColorFormat::Rgba16f => TextureFormat::Rgba16Float,
+        }
+    }
+}
+
+impl FromStr for ColorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bc7" => Ok(Self::Bc7),
+            "bc1" => Ok(Self::Bc1),
+            "rgba8" => Ok(Self::Rgba8),
+            "rgba8-srgb" => Ok(Self::Rgba8Srgb),
+            "rgba16f" => Ok(Self::Rgba16f),
+            _ => Err(format!(
+                "unknown texture format `{s}`, expected bc7, bc1, rgba8, rgba8-srgb or rgba16f"
+            )),
+        }
+    }
+}
+
+/// Parses `--texture-size`. Block compressed textures need whole 4x4 blocks at the base level,
+/// and `--texture-size-mix` halves the size, so it must be a power of two of at least 4.
+pub fn parse_texture_size(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(size) if size >= 4 && size.is_power_of_two() => Ok(size),
+        Ok(size) => Err(format!(
+            "texture size {size} must be a power of two of at least 4"
+        )),
+        Err(e) => Err(format!("invalid texture size `{value}`: {e}")),
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Pattern {
+    Bricks,
+    Tiles,
+    Noise,
+}
+
+pub fn generate_procedural_texture_with_mipmaps(
+    size: u32,
+    format: TextureFormat,
+    content: TextureContent,
+    seed: u32,
+) -> Image {
+    let (expected_size, mip_count) = calculate_image_size_with_mips(size, size, format);
+    // Single channel formats store the channel the content is sampled from.
+    let bc4_channel = match content {
+        TextureContent::MetallicRoughness => 1,
+        _ => 0,
+    };
+
+    let mut mip = generate_pattern(size, content, seed);
+    let mut mip_size = size;
+    let mut data = Vec::new();
+    for level in 0..mip_count {
+        if level > 0 {
+            mip = downsample(&mip, mip_size);
+            mip_size /= 2;
+        }
+        match format {
+            TextureFormat::Bc7RgbaUnorm | TextureFormat::Bc7RgbaUnormSrgb => {
+                encode_blocks(&mip, mip_size, &mut data, encode_bc7_block)
+            }
+            TextureFormat::Bc1RgbaUnorm | TextureFormat::Bc1RgbaUnormSrgb => {
+                encode_blocks(&mip, mip_size, &mut data, encode_bc1_block)
+            }
+            TextureFormat::Bc4RUnorm => encode_blocks(&mip, mip_size, &mut data, |block, data| {
+                encode_bc4_block(block, bc4_channel, data)
+            }),
+            TextureFormat::Bc5RgUnorm => encode_blocks(&mip, mip_size, &mut data, |block, data| {
+                encode_bc4_block(block, 0, data);
+                encode_bc4_block(block, 1, data);
+            }),
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => {
+                data.extend(mip.iter().flatten())
+            }
+            TextureFormat::Rgba16Float => data.extend(
+                mip.iter()
+                    .flatten()
+                    .flat_map(|&c| f32_to_f16_bits(c as f32 / 255.0).to_le_bytes()),
+            ),
+            _ => panic!("Unsupported procedural texture format {format:?}"),
+        }
+    }
+    debug_assert_eq!(data.len(), expected_size as usize);
+
+    Image {
+        texture_descriptor: TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: size,
+                height: size,
+                ..default()
+            },
+            dimension: TextureDimension::D2,
+            format,
+            mip_level_count: mip_count,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        sampler: ImageSampler::Descriptor(ImageSamplerDescriptor {
+            address_mode_u: ImageAddressMode::Repeat,
+            address_mode_v: ImageAddressMode::Repeat,
+            ..ImageSamplerDescriptor::linear()
+        }),
+        data,
+        ..Default::default()
+    }
+}
+
+/// Packs textures with the same size, format and mip count into layers of a 2D array texture.
+pub fn stack_texture_array(layers: &[&Image]) -> Image {
+    let first = layers[0];
+    let mut data = Vec::with_capacity(first.data.len() * layers.len());
+    for layer in layers {
+        assert_eq!(
+            layer.texture_descriptor.size, first.texture_descriptor.size,
+            "Texture array layers must have the same size"
+        );
+        assert_eq!(
+            layer.texture_descriptor.format,
+            first.texture_descriptor.format
+        );
+        // wgpu expects layer major order, every mip of a layer before the next layer.
+        data.extend_from_slice(&layer.data);
+    }
+
+    let mut image = Image {
+        data,
+        texture_view_descriptor: Some(TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::D2Array),
+            ..default()
+        }),
+        ..first.clone()
+    };
+    image.texture_descriptor.size.depth_or_array_layers = layers.len() as u32;
+    image
+}
+
+/// Size in bytes of a mip level, with its dimensions padded up to whole blocks.
+/// Works for uncompressed and any block compressed format (BCn, ETC2, EAC, ASTC).
+pub fn mip_level_size(width: u32, height: u32, format: TextureFormat, level: u32) -> u32 {
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format
+        .block_copy_size(None)
+        .unwrap_or_else(|| panic!("{format:?} has no single aspect block size"));
+    let (width, height) = ((width >> level).max(1), (height >> level).max(1));
+    width.div_ceil(block_width) * height.div_ceil(block_height) * block_size
+}
+
+/// Total size in bytes and mip count of a full mip chain, down to 1x1.
+pub fn calculate_image_size_with_mips(
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+) -> (u32, u32) {
+    let mip_count = 32 - width.max(height).max(1).leading_zeros();
+    let total_size = (0..mip_count)
+        .map(|level| mip_level_size(width, height, format, level))
+        .sum();
+    (total_size, mip_count)
+}
+
+fn generate_pattern(size: u32, content: TextureContent, seed: u32) -> Vec<[u8; 4]> {
+    let pattern = match uhash(seed, 0) % 3 {
+        0 => Pattern::Bricks,
+        1 => Pattern::Tiles,
+        _ => Pattern::Noise,
+    };
+    // Feature sizes are powers of two so every pattern tiles seamlessly.
+    let cell = (size / (4 << (uhash(seed, 1) % 4))).max(8);
+    let base = Vec3::new(
+        hash_noise(seed, 1, 0),
+        hash_noise(seed, 1, 1),
+        hash_noise(seed, 1, 2),
+    );
+    let joint = base * 0.35 + 0.45;
+
+    // (variation, grain, is_joint) where variation changes per brick/tile.
+    let mut surface = Vec::with_capacity((size * size) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let grain = value_noise(x, y, (cell / 4).max(2), size, seed) * 0.6
+                + value_noise(x, y, 2, size, seed + 1) * 0.4;
+            let (variation, is_joint) = match pattern {
+                Pattern::Bricks => {
+                    let row = y / (cell / 2);
+                    let offset = if row.is_multiple_of(2) { 0 } else { cell / 2 };
+                    let bx = ((x + offset) % size) / cell;
+                    let joint = (x + offset) % cell < 2 || y % (cell / 2) < 2;
+                    (hash_noise(bx, row, seed), joint)
+                }
+                Pattern::Tiles => {
+                    let (tx, ty) = (x / cell, y / cell);
+                    let joint = x % cell < 2 || y % cell < 2;
+                    let checker = ((tx + ty) % 2) as f32 * 0.5;
+                    (checker + hash_noise(tx, ty, seed) * 0.5, joint)
+                }
+                Pattern::Noise => (value_noise(x, y, cell, size, seed + 2), false),
+            };
+            surface.push((variation, grain, is_joint));
+        }
+    }
+    let height = |x: u32, y: u32| {
+        let (variation, grain, is_joint) = surface[((y % size) * size + x % size) as usize];
+        match (pattern, is_joint) {
+            (_, true) => 0.0,
+            (Pattern::Noise, _) => variation * 0.8 + grain * 0.2,
+            _ => 0.7 + grain * 0.3,
+        }
+    };
+
+    let mut pixels = Vec::with_capacity((size * size) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let (variation, grain, is_joint) = surface[(y * size + x) as usize];
+            let pixel = match content {
+                TextureContent::BaseColor => {
+                    let color = if is_joint {
+                        joint * (0.8 + grain * 0.2)
+                    } else {
+                        base * (0.55 + variation * 0.35 + grain * 0.2)
+                    };
+                    // Joints are cut out by alpha masked materials.
+                    color.extend(if is_joint { 0.0 } else { 1.0 })
+                }
+                TextureContent::MetallicRoughness => {
+                    let roughness = if is_joint {
+                        0.95
+                    } else {
+                        0.3 + variation * 0.4 + grain * 0.25
+                    };
+                    let metallic = if is_joint {
+                        0.0
+                    } else {
+                        (variation - 0.8).max(0.0) * 5.0
+                    };
+                    Vec4::new(1.0, roughness, metallic, 1.0)
+                }
+                TextureContent::Normal => {
+                    // Central differences of the wrapped height field.
+                    let dx = height(x + 1, y) - height(x + size - 1, y);
+                    let dy = height(x, y + 1) - height(x, y + size - 1);
+                    let normal = Vec3::new(-dx * 2.0, -dy * 2.0, 1.0).normalize();
+                    (normal * 0.5 + 0.5).extend(1.0)
+                }
+                TextureContent::Occlusion => {
+                    let occlusion = if is_joint { 0.35 } else { 0.8 + grain * 0.2 };
+                    Vec4::new(occlusion, occlusion, occlusion, 1.0)
+                }
+                TextureContent::Emissive => {
+                    let lit = !is_joint && variation > 0.9;
+                    if lit {
+                        (base * 0.5 + 0.5).extend(1.0)
+                    } else {
+                        Vec4::new(0.0, 0.0, 0.0, 1.0)
+                    }
+                }
+            };
+            pixels.push(
+                (pixel.clamp(Vec4::ZERO, Vec4::ONE) * 255.0)
+                    .round()
+                    .to_array()
+                    .map(|c| c as u8),
+            );
+        }
+    }
+    pixels
+}
+
+// Smoothly interpolated lattice noise with a lattice that wraps at `size`.
+fn value_noise(x: u32, y: u32, period: u32, size: u32, seed: u32) -> f32 {
+    let cells = (size / period).max(1);
+    let (cx, cy) = (x / period, y / period);
+    let fx = (x % period) as f32 / period as f32;
+    let fy = (y % period) as f32 / period as f32;
+    let (sx, sy) = (fx * fx * (3.0 - 2.0 * fx), fy * fy * (3.0 - 2.0 * fy));
+    let lattice = |ix: u32, iy: u32| hash_noise(ix % cells, iy % cells, seed);
+    let top = lattice(cx, cy) * (1.0 - sx) + lattice(cx + 1, cy) * sx;
+    let bottom = lattice(cx, cy + 1) * (1.0 - sx) + lattice(cx + 1, cy + 1) * sx;
+    top * (1.0 - sy) + bottom * sy
+}
+
+fn downsample(pixels: &[[u8; 4]], size: u32) -> Vec<[u8; 4]> {
+    let size = size as usize;
+    let half = (size / 2).max(1);
+    let mut out = Vec::with_capacity(half * half);
+    for y in 0..half {
+        for x in 0..half {
+            let mut sum = [0u32; 4];
+            for (sx, sy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let p = pixels[((y * 2 + sy).min(size - 1)) * size + (x * 2 + sx).min(size - 1)];
+                for c in 0..4 {
+                    sum[c] += p[c] as u32;
+                }
+            }
+            out.push(sum.map(|c| ((c + 2) / 4) as u8));
+        }
+    }
+    out
+}
+
+// Round to nearest, values too small for a normal f16 flush to zero. Only handles 0.0..=1.0.
+fn f32_to_f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let rounded = bits + 0x0fff + ((bits >> 13) & 1);
+    let exponent = ((rounded >> 23) & 0xff) as i32 - 127 + 15;
+    if exponent <= 0 {
+        return 0;
+    }
+    ((exponent as u32) << 10 | (rounded >> 13) & 0x3ff) as u16
+}
+
+fn encode_blocks(
+    pixels: &[[u8; 4]],
+    size: u32,
+    data: &mut Vec<u8>,
+    encode_block: impl Fn(&[[u8; 4]; 16], &mut Vec<u8>),
+) {
+    let size = size as usize;
+    for by in 0..size.div_ceil(4) {
+        for bx in 0..size.div_ceil(4) {
+            let mut block = [[0u8; 4]; 16];
+            for (i, texel) in block.iter_mut().enumerate() {
+                let (x, y) = (
+                    (bx * 4 + i % 4).min(size - 1),
+                    (by * 4 + i / 4).min(size - 1),
+                );
+                *texel = pixels[y * size + x];
+            }
+            encode_block(&block, data);
+        }
+    }
+}
+
+// Endpoints along the principal axis of the block's colors.
+fn principal_endpoints(block: &[[u8; 4]; 16], channels: usize) -> (Vec4, Vec4) {
+    let texels = block.map(|p| Vec4::from_array(p.map(|c| c as f32)));
+    let mask = Vec4::from_array([0, 1, 2, 3].map(|c| if c < channels { 1.0 } else { 0.0 }));
+    let mean = texels.iter().sum::<Vec4>() / 16.0 * mask;
+
+    let mut covariance = Mat4::ZERO;
+    for t in &texels {
+        let d = (*t * mask) - mean;
+        covariance += Mat4::from_cols(d * d.x, d * d.y, d * d.z, d * d.w);
+    }
+    let mut axis = Vec4::ONE * mask;
+    for _ in 0..8 {
+        axis = (covariance * axis).normalize_or_zero();
+    }
+    if axis == Vec4::ZERO {
+        return (mean, mean);
+    }
+
+    let (mut min, mut max) = (f32::MAX, f32::MIN);
+    for t in &texels {
+        let p = ((*t * mask) - mean).dot(axis);
+        min = min.min(p);
+        max = max.max(p);
+    }
+    (mean + axis * min, mean + axis * max)
+}
+
+fn nearest_index(palette: &[Vec4], texel: Vec4) -> usize {
+    let mut best = (f32::MAX, 0);
+    for (i, c) in palette.iter().enumerate() {
+        let error = (*c - texel).length_squared();
+        if error < best.0 {
+            best = (error, i);
+        }
+    }
+    best.1
+}
+
+fn encode_bc1_block(block: &[[u8; 4]; 16], data: &mut Vec<u8>) {
+    let to_565 = |c: Vec4| -> u16 {
+        let c = c.clamp(Vec4::ZERO, Vec4::splat(255.0));
+        ((c.x * 31.0 / 255.0).round() as u16) << 11
+            | ((c.y * 63.0 / 255.0).round() as u16) << 5
+            | (c.z * 31.0 / 255.0).round() as u16
+    };
+    let from_565 = |c: u16| {
+        Vec4::new(
+            ((c >> 11) & 31) as f32 * 255.0 / 31.0,
+            ((c >> 5) & 63) as f32 * 255.0 / 63.0,
+            (c & 31) as f32 * 255.0 / 31.0,
+            0.0,
+        )
+    };
+
+    let (e0, e1) = principal_endpoints(block, 3);
+    let (mut c0, mut c1) = (to_565(e1), to_565(e0));
+    // color0 > color1 selects the opaque 4 color mode, color0 <= color1 the 3 color mode where
+    // index 3 is transparent black, used when the block has texels with alpha below 128.
+    let transparent = block.iter().any(|texel| texel[3] < 128);
+    if (c0 < c1) != transparent {
+        std::mem::swap(&mut c0, &mut c1);
+    }
+    let (p0, p1) = (from_565(c0), from_565(c1));
+    let (palette, color_count) = if transparent {
+        ([p0, p1, (p0 + p1) / 2.0, Vec4::ZERO], 3)
+    } else {
+        ([p0, p1, (p0 * 2.0 + p1) / 3.0, (p0 + p1 * 2.0) / 3.0], 4)
+    };
+    let mut indices = 0u32;
+    if c0 != c1 || transparent {
+        for (i, texel) in block.iter().enumerate() {
+            let index = if texel[3] < 128 {
+                3
+            } else {
+                let t = Vec4::new(texel[0] as f32, texel[1] as f32, texel[2] as f32, 0.0);
+                nearest_index(&palette[..color_count], t) as u32
+            };
+            indices |= index << (i * 2);
+        }
+    }
+    data.extend_from_slice(&c0.to_le_bytes());
+    data.extend_from_slice(&c1.to_le_bytes());
+    data.extend_from_slice(&indices.to_le_bytes());
+}
+
+// Endpoints are the channel's min and max, with red0 > red1 selecting 8 interpolated values.
+fn encode_bc4_block(block: &[[u8; 4]; 16], channel: usize, data: &mut Vec<u8>) {
+    let values = block.map(|t| t[channel]);
+    let red0 = *values.iter().max().unwrap();
+    let red1 = *values.iter().min().unwrap();
+    let mut indices = 0u64;
+    if red0 > red1 {
+        // Weights of red1 per index, index 0 is red0, index 1 is red1, 2..7 interpolate between.
+        let palette = [0u32, 7, 1, 2, 3, 4, 5, 6]
+            .map(|w| Vec4::splat(((7 - w) * red0 as u32 + w * red1 as u32) as f32 / 7.0));
+        for (i, v) in values.iter().enumerate() {
+            let index = nearest_index(&palette, Vec4::splat(*v as f32)) as u64;
+            indices |= index << (i * 3);
+        }
+    }
+    data.push(red0);
+    data.push(red1);
+    data.extend_from_slice(&indices.to_le_bytes()[..6]);
+}
+
+// BC7 mode 6: a single subset with 7 bit RGBA endpoints, a p-bit per endpoint and 4 bit indices.
+fn encode_bc7_block(block: &[[u8; 4]; 16], data: &mut Vec<u8>) {
+    const WEIGHTS: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+    // Picks the p-bit and 7 bit channels that best represent the endpoint.
+    let quantize = |e: Vec4| -> ([u32; 4], u32) {
+        let e = e.clamp(Vec4::ZERO, Vec4::splat(255.0));
+        let mut best = ([0; 4], 0, f32::MAX);
+        for p in 0..2 {
+            let q = e
+                .to_array()
+                .map(|c| (((c - p as f32) / 2.0).round() as i32).clamp(0, 127) as u32);
+            let error = q
+                .iter()
+                .zip(e.to_array())
+                .map(|(q, c)| ((q << 1 | p) as f32 - c).powi(2))
+                .sum::<f32>();
+            if error < best.2 {
+                best = (q, p, error);
+            }
+        }
+        (best.0, best.1)
+    };
+
+    let (e0, e1) = principal_endpoints(block, 4);
+    let (mut q0, mut p0) = quantize(e0);
+    let (mut q1, mut p1) = quantize(e1);
+
+    let palette_and_indices = |q0: [u32; 4], p0: u32, q1: [u32; 4], p1: u32| {
+        let (a, b) = (q0.map(|c| c << 1 | p0), q1.map(|c| c << 1 | p1));
+        let palette = WEIGHTS.map(|w| {
+            Vec4::from_array([0, 1, 2, 3].map(|c| (((64 - w) * a[c] + w * b[c] + 32) >> 6) as f32))
+        });
+        block.map(|t| nearest_index(&palette, Vec4::from_array(t.map(|c| c as f32))) as u32)
+    };
+    let mut indices = palette_and_indices(q0, p0, q1, p1);
+    // The anchor (first) index has an implicit 0 high bit.
+    if indices[0] >= 8 {
+        std::mem::swap(&mut q0, &mut q1);
+        std::mem::swap(&mut p0, &mut p1);
+        indices = indices.map(|i| 15 - i);
+    }
+
+    let mut bits = 0u128;
+    let mut offset = 0;
+    let mut write = |value: u32, count: u32| {
+        bits |= (value as u128) << offset;
+        offset += count;
+    };
+    write(1 << 6, 7);
+    for c in 0..4 {
+        write(q0[c], 7);
+        write(q1[c], 7);
+    }
+    write(p0, 1);
+    write(p1, 1);
+    for (i, index) in indices.iter().enumerate() {
+        write(*index, if i == 0 { 3 } else { 4 });
+    }
+    data.extend_from_slice(&bits.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu_types::{AstcBlock, AstcChannel};
+
+    use super::*;
+
+    const FORMATS: [TextureFormat; 7] = [
+        TextureFormat::Bc1RgbaUnormSrgb,
+        TextureFormat::Bc4RUnorm,
+        TextureFormat::Bc7RgbaUnorm,
+        TextureFormat::Astc {
+            block: AstcBlock::B6x6,
+            channel: AstcChannel::UnormSrgb,
+        },
+        TextureFormat::Etc2Rgb8Unorm,
+        TextureFormat::Etc2Rgba8Unorm,
+        TextureFormat::Rgba8Unorm,
+    ];
+
+    // Square, rectangular, non power of two and tiny sizes.
+    const SIZES: [(u32, u32); 7] = [
+        (2048, 2048),
+        (2048, 512),
+        (512, 2048),
+        (1000, 600),
+        (1, 1),
+        (2, 2),
+        (5, 3),
+    ];
+
+    /// Size of a mip level using wgpu's own mip and block padding math.
+    fn wgpu_mip_level_size(width: u32, height: u32, format: TextureFormat, level: u32) -> u32 {
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        }
+        .mip_level_size(level, TextureDimension::D2)
+        .physical_size(format);
+        let (block_width, block_height) = format.block_dimensions();
+        size.width / block_width * size.height / block_height
+            * format.block_copy_size(None).unwrap()
+    }
+
+    #[test]
+    fn mip_level_size_matches_wgpu() {
+        for format in FORMATS {
+            for (width, height) in SIZES {
+                let mip_count = Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                }
+                .max_mips(TextureDimension::D2);
+                for level in 0..mip_count {
+                    assert_eq!(
+                        mip_level_size(width, height, format, level),
+                        wgpu_mip_level_size(width, height, format, level),
+                        "{format:?} {width}x{height} level {level}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mip_chain_matches_wgpu() {
+        for format in FORMATS {
+            for (width, height) in SIZES {
+                let expected_mip_count = Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                }
+                .max_mips(TextureDimension::D2);
+                let expected_size = (0..expected_mip_count)
+                    .map(|level| wgpu_mip_level_size(width, height, format, level))
+                    .sum();
+                assert_eq!(
+                    calculate_image_size_with_mips(width, height, format),
+                    (expected_size, expected_mip_count),
+                    "{format:?} {width}x{height}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn texture_size_is_a_power_of_two() {
+        for value in ["4", "64", "2048"] {
+            assert!(parse_texture_size(value).is_ok(), "{value}");
+        }
+        for value in ["0", "1", "2", "6", "12", "1000", "-4", "abc"] {
+            assert!(parse_texture_size(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn bc1_transparent_texels() {
+        let mut block = [[200, 100, 50, 255]; 16];
+        block[5] = [0, 0, 0, 0];
+        let mut data = Vec::new();
+        encode_bc1_block(&block, &mut data);
+        let c0 = u16::from_le_bytes([data[0], data[1]]);
+        let c1 = u16::from_le_bytes([data[2], data[3]]);
+        let indices = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+        assert!(c0 <= c1, "3 color mode");
+        assert_eq!((indices >> 10) & 3, 3, "transparent texel");
+        assert_ne!(indices & 3, 3, "opaque texel");
+
+        let mut data = Vec::new();
+        encode_bc1_block(&[[200, 100, 50, 255]; 16], &mut data);
+        let c0 = u16::from_le_bytes([data[0], data[1]]);
+        let c1 = u16::from_le_bytes([data[2], data[3]]);
+        assert!(c0 >= c1, "4 color mode");
+    }
+
+    #[test]
+    fn bc7_2048_mip_chain() {
+        let format = TextureFormat::Bc7RgbaUnorm;
+        let (size, mip_count) = calculate_image_size_with_mips(2048, 2048, format);
+        assert_eq!(mip_count, 12);
+        // 2x2 and 1x1 levels are padded to a whole 4x4 block of 16 bytes.
+        assert_eq!(mip_level_size(2048, 2048, format, 10), 16);
+        assert_eq!(mip_level_size(2048, 2048, format, 11), 16);
+        let expected = (0..10)
+            .map(|level| (512 >> level) * (512 >> level) * 16)
+            .sum::<u32>()
+            + 32;
+        assert_eq!(size, expected);
+    }
+}
@@ -0,0 +1,124 @@
+// Benchmark viewpoints generated from the scene bounds, so the benchmark works on any scene:
+// a ring of exterior views looking at the scene center, and interior views at the most open
+// points found by casting rays against the mesh instance bounds.
+
+use bevy::{math::bounding::BoundingVolume, prelude::*};
+
+use crate::{bounds::SceneBounds, hash_noise, Args};
+
+// Candidate points sampled for interior viewpoints.
+const INTERIOR_SAMPLES: u32 = 512;
+
+#[derive(Resource)]
+pub struct BenchmarkViewpoints(pub Vec<Transform>);
+
+pub struct ViewpointsPlugin;
+
+impl Plugin for ViewpointsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            generate_benchmark_viewpoints.run_if(resource_added::<SceneBounds>),
+        );
+    }
+}
+
+fn generate_benchmark_viewpoints(
+    mut commands: Commands,
+    bounds: Res<SceneBounds>,
+    args: Res<Args>,
+) {
+    let viewpoints = [
+        exterior_viewpoints(&bounds, args.exterior_viewpoints),
+        interior_viewpoints(&bounds, args.interior_viewpoints),
+    ]
+    .concat();
+    println!("Generated {} benchmark viewpoints:", viewpoints.len());
+    for viewpoint in &viewpoints {
+        println!(
+            "  translation {}, forward {}",
+            viewpoint.translation,
+            viewpoint.forward().as_vec3()
+        );
+    }
+    commands.insert_resource(BenchmarkViewpoints(viewpoints));
+}
+
+/// Evenly spaced around the scene, above and outside its bounds, looking at the center.
+pub fn exterior_viewpoints(bounds: &SceneBounds, count: usize) -> Vec<Transform> {
+    let center = Vec3::from(bounds.total.center());
+    let half_size = Vec3::from(bounds.total.half_size());
+    let radius = half_size.xz().length() * 1.5;
+    (0..count)
+        .map(|i| {
+            let angle = i as f32 / count as f32 * std::f32::consts::TAU;
+            let position = center
+                + Vec3::new(
+                    angle.cos() * radius,
+                    half_size.y * 1.5,
+                    angle.sin() * radius,
+                );
+            Transform::from_translation(position).looking_at(center, Vec3::Y)
+        })
+        .collect()
+}
+
+/// The points with the most clearance to surrounding mesh instances, out of deterministic random
+/// samples inside the scene bounds. Points must be enclosed horizontally, so they are inside.
+/// Each looks in the horizontal direction with the most space.
+pub fn interior_viewpoints(bounds: &SceneBounds, count: usize) -> Vec<Transform> {
+    let min = Vec3::from(bounds.total.min);
+    let size = Vec3::from(bounds.total.max - bounds.total.min);
+    let horizontal = [Dir3::X, Dir3::NEG_X, Dir3::Z, Dir3::NEG_Z];
+
+    let mut candidates = (0..INTERIOR_SAMPLES)
+        .filter_map(|i| {
+            let position = min
+                + size
+                    * Vec3::new(
+                        hash_noise(i, 0, 0),
+                        hash_noise(i, 0, 1),
+                        hash_noise(i, 0, 2),
+                    );
+            // Nearest bounds in each direction, ignoring bounds the point is inside of.
+            let distance = |direction: Dir3| {
+                bounds
+                    .bvh
+                    .ray_candidates(Ray3d::new(position, *direction))
+                    .into_iter()
+                    .map(|(_, distance)| distance)
+                    .filter(|distance| *distance > 0.0)
+                    .min_by(|a, b| a.total_cmp(b))
+            };
+            let distances = horizontal
+                .iter()
+                .map(|direction| distance(*direction))
+                .collect::<Option<Vec<_>>>()?;
+            let floor = distance(Dir3::NEG_Y)?;
+            let ceiling = distance(Dir3::Y)?;
+            let clearance = distances.iter().copied().fold(floor.min(ceiling), f32::min);
+            let (direction, _) = horizontal
+                .iter()
+                .zip(distances)
+                .max_by(|a, b| a.1.total_cmp(&b.1))?;
+            Some((clearance, position, *direction))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    // Keep viewpoints apart so they don't all end up in the same room.
+    let min_separation = size.length() * 0.1;
+    let mut viewpoints: Vec<Transform> = Vec::new();
+    for (_, position, direction) in candidates {
+        if viewpoints.len() == count {
+            break;
+        }
+        if viewpoints
+            .iter()
+            .all(|viewpoint| viewpoint.translation.distance(position) > min_separation)
+        {
+            viewpoints.push(Transform::from_translation(position).looking_to(direction, Vec3::Y));
+        }
+    }
+    viewpoints
+}
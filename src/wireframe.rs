@@ -0,0 +1,105 @@
+// Wireframe overlay using bevy's `WireframePlugin`, press X to cycle between off, all meshes and
+// only the mesh under the cursor.
+// Requires `WgpuFeatures::POLYGON_MODE_LINE`, the overlay stays off when it isn't supported.
+
+use bevy::{
+    pbr::wireframe::{Wireframe, WireframeConfig, WireframePlugin},
+    prelude::*,
+    render::{render_resource::WgpuFeatures, renderer::RenderDevice},
+    window::PrimaryWindow,
+};
+
+use crate::{
+    bounds::SceneBounds,
+    picking::{cursor_ray, pick_scene_mesh, PickableInstances},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum WireframeMode {
+    #[default]
+    Off,
+    Global,
+    UnderCursor,
+}
+
+#[derive(Resource, Default)]
+pub struct WireframeOverlay {
+    pub mode: WireframeMode,
+    /// Mesh instance with a `Wireframe` in `UnderCursor` mode.
+    pub entity: Option<Entity>,
+}
+
+pub struct WireframeOverlayPlugin;
+
+impl Plugin for WireframeOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(WireframePlugin)
+            .init_resource::<WireframeOverlay>()
+            .add_systems(
+                Update,
+                (toggle_wireframe_overlay, wireframe_under_cursor).chain(),
+            );
+    }
+}
+
+fn toggle_wireframe_overlay(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    mut overlay: ResMut<WireframeOverlay>,
+    mut config: ResMut<WireframeConfig>,
+    render_device: Option<Res<RenderDevice>>,
+) {
+    if !input.just_pressed(KeyCode::KeyX) {
+        return;
+    }
+    let supported = render_device
+        .is_some_and(|device| device.features().contains(WgpuFeatures::POLYGON_MODE_LINE));
+    if !supported {
+        println!(
+            "Wireframe overlay unavailable, POLYGON_MODE_LINE is not supported by this backend"
+        );
+        return;
+    }
+
+    overlay.mode = match overlay.mode {
+        WireframeMode::Off => WireframeMode::Global,
+        WireframeMode::Global => WireframeMode::UnderCursor,
+        WireframeMode::UnderCursor => WireframeMode::Off,
+    };
+    config.global = overlay.mode == WireframeMode::Global;
+    if let Some(entity) = overlay.entity.take() {
+        if let Some(mut entity) = commands.get_entity(entity) {
+            entity.remove::<Wireframe>();
+        }
+    }
+    println!("Wireframe overlay: {:?}", overlay.mode);
+}
+
+fn wireframe_under_cursor(
+    mut commands: Commands,
+    mut overlay: ResMut<WireframeOverlay>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    instances: PickableInstances,
+    meshes: Res<Assets<Mesh>>,
+    bounds: Option<Res<SceneBounds>>,
+) {
+    if overlay.mode != WireframeMode::UnderCursor {
+        return;
+    }
+    let picked = cursor_ray(&windows, &camera)
+        .and_then(|ray| pick_scene_mesh(ray, &instances, &meshes, bounds.as_deref()))
+        .map(|(entity, _)| entity);
+    if picked == overlay.entity {
+        return;
+    }
+    if let Some(entity) = overlay.entity {
+        if let Some(mut entity) = commands.get_entity(entity) {
+            entity.remove::<Wireframe>();
+        }
+    }
+    if let Some(entity) = picked {
+        commands.entity(entity).insert(Wireframe);
+    }
+    overlay.entity = picked;
+}